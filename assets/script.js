@@ -10,7 +10,7 @@ function queryArgsFromForm(form) {
     const items = [];
     for (const {name, value, type, checked} of form.elements) {
         if (!name) continue;
-        if (type === "radio" && !checked) continue;
+        if ((type === "radio" || type === "checkbox") && !checked) continue;
         items.push(encodeURIComponent(name) + '=' + encodeURIComponent(value));
     }
     return items.join('&');
@@ -77,6 +77,7 @@ function openEditor() {
     const cancel = form.querySelector('.cancel');
     const cancelButton = form.querySelector('button.button-cancel');
     const cancelInteractionGroup = form.querySelector(".cancel-interaction-group");
+    const previewToggle = form.querySelector('.preview-toggle');
 
     const footer = document.querySelector("footer");
     const lastUpdated = footer.querySelector(".last-updated");
@@ -95,6 +96,70 @@ function openEditor() {
     textarea.addEventListener('input', () => autosizeTextarea(textarea, shadow));
     window.addEventListener('resize', () => autosizeTextarea(textarea, shadow));
 
+    // The preview replaces the rendered article while editing, which is
+    // restored when the edit is cancelled
+    const preview = {
+        delay: 500,
+        timer: null,
+        latest: 0,
+        savedRendered: null,
+    };
+
+    function schedulePreview(delay) {
+        clearTimeout(preview.timer);
+        if (!previewToggle.checked) return;
+        preview.timer = setTimeout(updatePreview, delay);
+    }
+
+    function updatePreview() {
+        const request = ++preview.latest;
+        const body = "title=" + encodeURIComponent(form.elements.title.value) +
+            "&body=" + encodeURIComponent(textarea.value);
+
+        fetch(
+            "_preview",
+            {
+                method: 'POST',
+                headers: {
+                    "Content-Type": "application/x-www-form-urlencoded"
+                },
+                body: body,
+                credentials: "same-origin",
+            }
+        ).then(response => {
+            if (request !== preview.latest) return;
+
+            if (response.status === 429) {
+                const retryAfter = parseInt(response.headers.get("retry-after"), 10) || 10;
+                return schedulePreview(retryAfter * 1000);
+            }
+
+            if (!response.ok) throw new Error("Unexpected status code (" + response.status + ")");
+
+            return response.text().then(html => {
+                if (request !== preview.latest || !previewToggle.checked) return;
+                if (preview.savedRendered === null) preview.savedRendered = rendered.innerHTML;
+                rendered.innerHTML = html;
+            });
+        }).catch(err => console.error(err));
+    }
+
+    function restoreRendered() {
+        clearTimeout(preview.timer);
+        preview.latest++;
+        if (preview.savedRendered !== null) rendered.innerHTML = preview.savedRendered;
+        preview.savedRendered = null;
+    }
+
+    textarea.addEventListener('input', () => schedulePreview(preview.delay));
+    form.elements.title.addEventListener('input', () => schedulePreview(preview.delay));
+
+    previewToggle.addEventListener('change', () => {
+        retainScrollRatio(() => container.classList.toggle('preview', previewToggle.checked));
+        if (previewToggle.checked) schedulePreview(0);
+        else restoreRendered();
+    });
+
     function updateFormEnabledState() {
         const baseEnabled = !state.saving && state.editing();
         const enabled = {
@@ -154,6 +219,10 @@ function openEditor() {
                     });
             }
 
+            if (response.status === 409) {
+                return response.text().then(text => { throw new Error(text); });
+            }
+
             if (!response.ok) throw new Error("Unexpected status code (" + response.status + ")");
 
             return response.json()
@@ -167,11 +236,16 @@ function openEditor() {
                     lastUpdated.classList.remove("missing");
 
                     // Update body:
+                    restoreRendered();
                     rendered.innerHTML = result.rendered;
 
                     form.elements.title.value = result.title;
                     shadow.value = textarea.value = result.body;
 
+                    // The response has the whole body, also when editing a single section
+                    const section = form.querySelector('input[name="section"]');
+                    if (section) section.parentNode.removeChild(section);
+
                     form.querySelector(`.theme-picker--option[value=${JSON.stringify(result.theme)}]`).checked = true;
                     bodyElement.className = `theme-${result.theme}`;
 
@@ -183,6 +257,8 @@ function openEditor() {
                     }
 
                     if (!result.conflict) {
+                        form.elements.summary.value = form.elements.summary.defaultValue = "";
+                        form.elements.minor.checked = form.elements.minor.defaultChecked = false;
                         closeEditor();
                     }
 
@@ -211,6 +287,8 @@ function openEditor() {
                     closeEditor();
                     updateFormEnabledState();
                     form.reset();
+                    restoreRendered();
+                    container.classList.toggle('preview', previewToggle.checked);
 
                     let selectedTheme = form.querySelector(`.theme-picker--option[checked]`).value;
                     bodyElement.className = `theme-${selectedTheme}`;
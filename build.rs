@@ -4,8 +4,10 @@ extern crate diesel;
 use diesel::prelude::*;
 use diesel::Connection;
 use quote::quote;
+use std::collections::hash_map::DefaultHasher;
 use std::env;
 use std::fs::File;
+use std::hash::{Hash, Hasher};
 use std::io::prelude::*;
 use std::path::Path;
 use walkdir::WalkDir;
@@ -14,6 +16,9 @@ use walkdir::WalkDir;
 mod sqlfunc {
     use diesel::sql_types::Text;
     sql_function!(fn markdown_to_fts(text: Text) -> Text);
+    sql_function!(fn markdown_to_links(text: Text) -> Text);
+    sql_function!(fn markdown_to_tags(text: Text) -> Text);
+    sql_function!(fn markdown_to_properties(text: Text) -> Text);
     sql_function!(fn theme_from_str_hash(text: Text) -> Text);
 }
 
@@ -36,6 +41,16 @@ fn main() {
 
     sqlfunc::markdown_to_fts::register_impl(&connection, |_: String| -> String { unreachable!() })
         .unwrap();
+    sqlfunc::markdown_to_links::register_impl(&connection, |_: String| -> String {
+        unreachable!()
+    })
+    .unwrap();
+    sqlfunc::markdown_to_tags::register_impl(&connection, |_: String| -> String { unreachable!() })
+        .unwrap();
+    sqlfunc::markdown_to_properties::register_impl(&connection, |_: String| -> String {
+        unreachable!()
+    })
+    .unwrap();
     sqlfunc::theme_from_str_hash::register_impl(&connection, |_: String| -> String {
         unreachable!()
     })
@@ -57,6 +72,23 @@ fn main() {
                 infer_table_from_schema!(#db_path, "article_revisions");
             }
             pub use self::__diesel_infer_schema_article_revisions::*;
+
+            mod __diesel_infer_schema_links {
+                infer_table_from_schema!(#db_path, "links");
+            }
+            pub use self::__diesel_infer_schema_links::*;
+            mod __diesel_infer_schema_tags {
+                infer_table_from_schema!(#db_path, "tags");
+            }
+            pub use self::__diesel_infer_schema_tags::*;
+            mod __diesel_infer_schema_properties {
+                infer_table_from_schema!(#db_path, "properties");
+            }
+            pub use self::__diesel_infer_schema_properties::*;
+            mod __diesel_infer_schema_render_cache {
+                infer_table_from_schema!(#db_path, "render_cache");
+            }
+            pub use self::__diesel_infer_schema_render_cache::*;
         }
         .to_string()
         .as_bytes(),
@@ -74,4 +106,15 @@ fn main() {
     for env_var in &["CONTINUOUS_INTEGRATION", "TRAVIS_BRANCH", "TRAVIS_COMMIT"] {
         println!("cargo:rerun-if-env-changed={}", env_var);
     }
+
+    // Rendered articles are cached by a hash of the renderer, so the cache
+    // is refreshed whenever it changes
+    let mut hasher = DefaultHasher::new();
+    for path in &["src/rendering.rs", "src/math.rs", "src/highlight.rs"] {
+        println!("cargo:rerun-if-changed={}", path);
+        std::fs::read(path)
+            .expect("Unable to read renderer source")
+            .hash(&mut hasher);
+    }
+    println!("cargo:rustc-env=RENDERER_HASH={:016x}", hasher.finish());
 }
@@ -0,0 +1,14 @@
+ALTER TABLE article_revisions ADD COLUMN deleted BOOLEAN NOT NULL DEFAULT 0;
+
+-- Deleted articles keep their latest revision, a tombstone, but must not be
+-- searchable:
+DROP TRIGGER article_revisions_ai;
+DROP TRIGGER article_revisions_au_enable;
+
+CREATE TRIGGER article_revisions_ai AFTER INSERT ON article_revisions WHEN new.latest = 1 BEGIN
+  DELETE FROM article_search WHERE rowid = new.article_id;
+  INSERT INTO article_search(rowid, title, body, slug) SELECT new.article_id, new.title, markdown_to_fts(new.body), new.slug WHERE new.deleted = 0;
+END;
+CREATE TRIGGER article_revisions_au_enable AFTER UPDATE ON article_revisions WHEN old.latest = 0 AND new.latest = 1 AND new.deleted = 0 BEGIN
+  INSERT INTO article_search(rowid, title, body, slug) VALUES (new.article_id, new.title, markdown_to_fts(new.body), new.slug);
+END;
@@ -0,0 +1,2 @@
+-- Set when a revision is the result of reverting to an earlier revision
+ALTER TABLE article_revisions ADD COLUMN restored_revision INTEGER;
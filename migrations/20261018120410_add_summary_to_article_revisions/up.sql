@@ -0,0 +1,2 @@
+-- An optional description of the change made in a revision
+ALTER TABLE article_revisions ADD COLUMN summary TEXT;
@@ -0,0 +1,2 @@
+-- Set when the author marked the revision as a minor edit
+ALTER TABLE article_revisions ADD COLUMN minor BOOLEAN NOT NULL DEFAULT 0;
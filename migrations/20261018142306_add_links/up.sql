@@ -0,0 +1,21 @@
+CREATE TABLE links (
+    source_article_id INTEGER NOT NULL REFERENCES articles (id),
+    target_slug TEXT NOT NULL,
+    PRIMARY KEY (source_article_id, target_slug)
+);
+
+CREATE INDEX links_target_slug ON links (target_slug);
+
+-- markdown_to_links gives the link targets of a body separated by newlines
+WITH RECURSIVE split(source_article_id, target_slug, rest) AS (
+    SELECT article_id, '', markdown_to_links(body) FROM article_revisions
+        WHERE latest = 1 AND deleted = 0
+    UNION ALL
+    SELECT
+        source_article_id,
+        substr(rest, 1, instr(rest, char(10)) - 1),
+        substr(rest, instr(rest, char(10)) + 1)
+    FROM split WHERE rest <> ''
+)
+INSERT INTO links (source_article_id, target_slug)
+    SELECT source_article_id, target_slug FROM split WHERE target_slug <> '';
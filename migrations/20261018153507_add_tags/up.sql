@@ -0,0 +1,21 @@
+CREATE TABLE tags (
+    article_id INTEGER NOT NULL REFERENCES articles (id),
+    tag TEXT NOT NULL,
+    PRIMARY KEY (article_id, tag)
+);
+
+CREATE INDEX tags_tag ON tags (tag);
+
+-- markdown_to_tags gives the tags of a body separated by newlines
+WITH RECURSIVE split(article_id, tag, rest) AS (
+    SELECT article_id, '', markdown_to_tags(body) FROM article_revisions
+        WHERE latest = 1 AND deleted = 0
+    UNION ALL
+    SELECT
+        article_id,
+        substr(rest, 1, instr(rest, char(10)) - 1),
+        substr(rest, instr(rest, char(10)) + 1)
+    FROM split WHERE rest <> ''
+)
+INSERT INTO tags (article_id, tag)
+    SELECT article_id, tag FROM split WHERE tag <> '';
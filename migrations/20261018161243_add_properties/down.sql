@@ -0,0 +1 @@
+DROP TABLE properties;
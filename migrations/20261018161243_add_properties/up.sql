@@ -0,0 +1,27 @@
+CREATE TABLE properties (
+    article_id INTEGER NOT NULL REFERENCES articles (id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (article_id, key)
+);
+
+CREATE INDEX properties_key_value ON properties (key, value);
+
+-- markdown_to_properties gives a line for each property of a body, with the
+-- key and value separated by a tab
+WITH RECURSIVE split(article_id, line, rest) AS (
+    SELECT article_id, '', markdown_to_properties(body) FROM article_revisions
+        WHERE latest = 1 AND deleted = 0
+    UNION ALL
+    SELECT
+        article_id,
+        substr(rest, 1, instr(rest, char(10)) - 1),
+        substr(rest, instr(rest, char(10)) + 1)
+    FROM split WHERE rest <> ''
+)
+INSERT INTO properties (article_id, key, value)
+    SELECT
+        article_id,
+        substr(line, 1, instr(line, char(9)) - 1),
+        substr(line, instr(line, char(9)) + 1)
+    FROM split WHERE line <> '';
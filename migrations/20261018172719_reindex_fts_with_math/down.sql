@@ -0,0 +1,2 @@
+-- The search index is derived data, so there is nothing to undo
+SELECT 1;
@@ -0,0 +1,5 @@
+-- Rebuild the search index, so math is indexed as its TeX source
+DELETE FROM article_search;
+INSERT INTO article_search(rowid, title, body, slug)
+    SELECT article_id, title, markdown_to_fts(body), slug FROM article_revisions
+    WHERE latest = 1 AND deleted = 0;
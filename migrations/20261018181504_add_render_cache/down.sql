@@ -0,0 +1,2 @@
+DROP TRIGGER render_cache_invalidate;
+DROP TABLE render_cache;
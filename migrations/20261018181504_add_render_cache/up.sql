@@ -0,0 +1,19 @@
+CREATE TABLE render_cache (
+    article_id INTEGER NOT NULL REFERENCES articles (id),
+    revision INTEGER NOT NULL,
+    renderer_version TEXT NOT NULL,
+    html TEXT NOT NULL,
+    PRIMARY KEY (article_id, revision)
+);
+
+-- Links to missing articles are marked, so anything that changes which slugs
+-- exist invalidates the cache: a new article, a new slug or a (un)deletion
+CREATE TRIGGER render_cache_invalidate AFTER INSERT ON article_revisions
+WHEN NOT EXISTS (
+    SELECT 1 FROM article_revisions
+    WHERE article_id = new.article_id AND revision = new.revision - 1
+        AND slug = new.slug AND deleted = new.deleted
+)
+BEGIN
+    DELETE FROM render_cache;
+END;
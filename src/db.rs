@@ -16,6 +16,9 @@ struct SqliteInitializer;
 pub mod sqlfunc {
     use diesel::sql_types::Text;
     sql_function!(fn markdown_to_fts(text: Text) -> Text);
+    sql_function!(fn markdown_to_links(text: Text) -> Text);
+    sql_function!(fn markdown_to_tags(text: Text) -> Text);
+    sql_function!(fn markdown_to_properties(text: Text) -> Text);
     sql_function!(fn theme_from_str_hash(text: Text) -> Text);
 }
 
@@ -30,6 +33,21 @@ impl CustomizeConnection<SqliteConnection, r2d2_diesel::Error> for SqliteInitial
         })
         .map_err(r2d2_diesel::Error::QueryError)?;
 
+        sqlfunc::markdown_to_links::register_impl(conn, |text: String| {
+            rendering::markdown_to_links(&text)
+        })
+        .map_err(r2d2_diesel::Error::QueryError)?;
+
+        sqlfunc::markdown_to_tags::register_impl(conn, |text: String| {
+            rendering::markdown_to_tags(&text)
+        })
+        .map_err(r2d2_diesel::Error::QueryError)?;
+
+        sqlfunc::markdown_to_properties::register_impl(conn, |text: String| {
+            rendering::markdown_to_properties(&text)
+        })
+        .map_err(r2d2_diesel::Error::QueryError)?;
+
         sqlfunc::theme_from_str_hash::register_impl(conn, |title: String| {
             theme::theme_from_str_hash(&title)
         })
@@ -88,6 +106,57 @@ mod test {
         assert_eq!(expected, res[0].text);
     }
 
+    #[test]
+    fn markdown_to_links() {
+        let conn = test_connection();
+
+        #[derive(QueryableByName, PartialEq, Eq, Debug)]
+        struct Row {
+            #[sql_type = "Text"]
+            text: String,
+        }
+
+        let res = sql_query("SELECT markdown_to_links('[link](url) [Other link]') as text")
+            .load::<Row>(&conn)
+            .unwrap();
+
+        assert_eq!("other-link\nurl\n", res[0].text);
+    }
+
+    #[test]
+    fn markdown_to_tags() {
+        let conn = test_connection();
+
+        #[derive(QueryableByName, PartialEq, Eq, Debug)]
+        struct Row {
+            #[sql_type = "Text"]
+            text: String,
+        }
+
+        let res = sql_query("SELECT markdown_to_tags('---\ntags: b, a\n---\n') as text")
+            .load::<Row>(&conn)
+            .unwrap();
+
+        assert_eq!("a\nb\n", res[0].text);
+    }
+
+    #[test]
+    fn markdown_to_properties() {
+        let conn = test_connection();
+
+        #[derive(QueryableByName, PartialEq, Eq, Debug)]
+        struct Row {
+            #[sql_type = "Text"]
+            text: String,
+        }
+
+        let res = sql_query("SELECT markdown_to_properties('---\nstatus: draft\n---\n') as text")
+            .load::<Row>(&conn)
+            .unwrap();
+
+        assert_eq!("status\tdraft\n", res[0].text);
+    }
+
     #[test]
     fn theme_from_str_hash() {
         let conn = test_connection();
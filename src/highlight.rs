@@ -0,0 +1,278 @@
+// A small highlighter for the languages that are common in runbooks. It
+// recognises comments, strings, numbers, keywords and a few
+// language-specific tokens and wraps them in `<span class="hl-...">`.
+
+struct Language {
+    // Words separated by whitespace
+    keywords: &'static str,
+    literals: &'static str,
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    case_insensitive: bool,
+    dialect: Dialect,
+}
+
+#[derive(PartialEq)]
+enum Dialect {
+    Rust,
+    Shell,
+    Sql,
+}
+
+const RUST: Language = Language {
+    keywords: "as async await break const continue crate dyn else enum extern fn for if impl \
+        in let loop match mod move mut pub ref return self Self static struct super \
+        trait type unsafe use where while",
+    literals: "true false",
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['"'],
+    case_insensitive: false,
+    dialect: Dialect::Rust,
+};
+
+const SHELL: Language = Language {
+    keywords: "case do done elif else esac export fi for function if in local readonly return \
+        select then until while",
+    literals: "true false",
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &['"', '\''],
+    case_insensitive: false,
+    dialect: Dialect::Shell,
+};
+
+const SQL: Language = Language {
+    keywords: "add all alter and as asc begin between by case commit create delete desc \
+        distinct drop else end exists from group having in index inner insert into is \
+        join key left like limit not offset on or order outer primary references \
+        returning right rollback select set table then transaction union update values \
+        when where with",
+    literals: "null true false",
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &['\''],
+    case_insensitive: true,
+    dialect: Dialect::Sql,
+};
+
+fn language(name: &str) -> Option<&'static Language> {
+    match name.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "sh" | "bash" | "shell" | "zsh" | "console" => Some(&SHELL),
+        "sql" | "sqlite" | "postgresql" | "mysql" => Some(&SQL),
+        _ => None,
+    }
+}
+
+fn escape_html(src: &str) -> String {
+    src.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The length of the string starting at the beginning of `src`, including the
+// quotes. Unterminated strings extend to the end.
+fn string_len(src: &str, quote: char, escapes: bool) -> usize {
+    let mut chars = src.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return i + c.len_utf8();
+        }
+        if c == '\\' && escapes {
+            chars.next();
+        }
+    }
+    src.len()
+}
+
+// `'a'` and `'\n'` are characters, but `'a` is a lifetime
+fn rust_char_len(src: &str) -> Option<usize> {
+    let mut chars = src.char_indices().skip(1);
+    match chars.next()? {
+        (_, '\\') => {
+            chars.next()?;
+            chars.find(|&(_, c)| c == '\'').map(|(i, _)| i + 1)
+        }
+        (_, '\'') => None,
+        _ => match chars.next()? {
+            (i, '\'') => Some(i + 1),
+            _ => None,
+        },
+    }
+}
+
+// The length of the token at the start of `src` and its class, if it is
+// highlighted
+fn next_token(lang: &Language, src: &str, at_word_start: bool) -> (usize, Option<&'static str>) {
+    let first = src.chars().next().expect("src is not empty");
+
+    for comment in lang.line_comments {
+        // In shell, `#` only starts a comment at the start of a word
+        let starts_comment =
+            src.starts_with(comment) && (lang.dialect != Dialect::Shell || at_word_start);
+        if starts_comment {
+            return (src.find('\n').unwrap_or(src.len()), Some("hl-comment"));
+        }
+    }
+
+    if let Some((start, end)) = lang.block_comment {
+        if let Some(rest) = src.strip_prefix(start) {
+            let len = rest
+                .find(end)
+                .map(|i| start.len() + i + end.len())
+                .unwrap_or(src.len());
+            return (len, Some("hl-comment"));
+        }
+    }
+
+    if lang.quotes.contains(&first) {
+        // Single quotes in shell and SQL strings have no escapes
+        let escapes = first == '"';
+        return (string_len(src, first, escapes), Some("hl-string"));
+    }
+
+    if lang.dialect == Dialect::Rust && first == '\'' {
+        if let Some(len) = rust_char_len(src) {
+            return (len, Some("hl-string"));
+        }
+    }
+
+    if let (Dialect::Shell, Some(rest)) = (&lang.dialect, src.strip_prefix('$')) {
+        let len = if rest.starts_with('{') {
+            rest.find('}').map(|i| i + 1).unwrap_or(rest.len())
+        } else if rest.starts_with(|c: char| c.is_ascii_digit() || "?#@*$!-".contains(c)) {
+            1
+        } else {
+            rest.find(|c: char| !is_identifier(c)).unwrap_or(rest.len())
+        };
+        if len > 0 {
+            return (1 + len, Some("hl-variable"));
+        }
+    }
+
+    if first.is_ascii_digit() && at_word_start {
+        let len = src
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(src.len());
+        return (len, Some("hl-number"));
+    }
+
+    if is_identifier(first) {
+        let len = src.find(|c: char| !is_identifier(c)).unwrap_or(src.len());
+        let word = &src[..len];
+
+        let is_word = |words: &str| {
+            words.split_whitespace().any(|x| {
+                if lang.case_insensitive {
+                    x.eq_ignore_ascii_case(word)
+                } else {
+                    x == word
+                }
+            })
+        };
+
+        if !at_word_start {
+            return (len, None);
+        }
+        if is_word(lang.keywords) {
+            return (len, Some("hl-keyword"));
+        }
+        if is_word(lang.literals) {
+            return (len, Some("hl-literal"));
+        }
+        if lang.dialect == Dialect::Rust {
+            if src[len..].starts_with('!') {
+                return (len + 1, Some("hl-macro"));
+            }
+            if first.is_uppercase() {
+                return (len, Some("hl-type"));
+            }
+        }
+        return (len, None);
+    }
+
+    (first.len_utf8(), None)
+}
+
+// Highlights `code` as HTML if `lang` is known
+pub fn highlight(lang: &str, code: &str) -> Option<String> {
+    let lang = language(lang)?;
+
+    let mut buf = String::new();
+    let mut rest = code;
+    let mut at_word_start = true;
+
+    while !rest.is_empty() {
+        let (len, class) = next_token(lang, rest, at_word_start);
+        let (token, tail) = rest.split_at(len);
+
+        match class {
+            Some(class) => buf.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                class,
+                escape_html(token)
+            )),
+            None => buf.push_str(&escape_html(token)),
+        }
+
+        // In shell, `a#b` and `x=1` are not a comment and a number
+        let last = token.chars().last().expect("Tokens are not empty");
+        at_word_start = !is_identifier(last)
+            && (lang.dialect != Dialect::Shell || last.is_whitespace() || ";|&(".contains(last));
+        rest = tail;
+    }
+
+    Some(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unknown_language() {
+        assert_eq!(highlight("brainfuck", "+[-]"), None);
+    }
+
+    #[test]
+    fn rust() {
+        let actual = highlight(
+            "rust",
+            "fn f<'a>() -> u8 { println!(\"<\\\"\"); 'x' as u8 } // 1",
+        )
+        .unwrap();
+        let expected = "<span class=\"hl-keyword\">fn</span> f&lt;'a&gt;() -&gt; u8 { \
+            <span class=\"hl-macro\">println!</span>(<span class=\"hl-string\">\"&lt;\\\"\"</span>); \
+            <span class=\"hl-string\">'x'</span> <span class=\"hl-keyword\">as</span> u8 } \
+            <span class=\"hl-comment\">// 1</span>";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn shell() {
+        let actual =
+            highlight("sh", "if [ \"$x\" ]; then echo a#b $HOME ${y} 'q' # c\nfi").unwrap();
+        let expected = "<span class=\"hl-keyword\">if</span> [ <span class=\"hl-string\">\"$x\"</span> ]; \
+            <span class=\"hl-keyword\">then</span> echo a#b <span class=\"hl-variable\">$HOME</span> \
+            <span class=\"hl-variable\">${y}</span> <span class=\"hl-string\">'q'</span> \
+            <span class=\"hl-comment\"># c</span>\n<span class=\"hl-keyword\">fi</span>";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn sql() {
+        let actual = highlight("SQL", "select x1 FROM t WHERE y = 'it''s' -- 2\nLIMIT 10").unwrap();
+        let expected = "<span class=\"hl-keyword\">select</span> x1 \
+            <span class=\"hl-keyword\">FROM</span> t <span class=\"hl-keyword\">WHERE</span> y = \
+            <span class=\"hl-string\">'it'</span><span class=\"hl-string\">'s'</span> \
+            <span class=\"hl-comment\">-- 2</span>\n\
+            <span class=\"hl-keyword\">LIMIT</span> <span class=\"hl-number\">10</span>";
+        assert_eq!(actual, expected);
+    }
+}
@@ -34,9 +34,12 @@ use std::net::{IpAddr, SocketAddr};
 mod assets;
 mod build_config;
 mod db;
+mod highlight;
+mod math;
 mod merge;
 mod mimes;
 mod models;
+mod rate_limit;
 mod rendering;
 mod resources;
 mod schema;
@@ -51,11 +54,19 @@ pub fn main(
     bind_host: IpAddr,
     bind_port: u16,
     trust_identity: bool,
+    max_rebase_distance: i32,
+    min_toc_headings: usize,
+    allowed_schemes: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let db_pool = db::create_pool(db_file)?;
     let cpu_pool = futures_cpupool::CpuPool::new_num_cpus();
 
-    let state = state::State::new(db_pool, cpu_pool);
+    let settings = state::Settings {
+        max_rebase_distance,
+        min_toc_headings,
+        allowed_schemes,
+    };
+    let state = state::State::new(db_pool, cpu_pool, settings);
     let lookup = wiki_lookup::WikiLookup::new(state, trust_identity);
 
     let server = hyper::server::Http::new()
@@ -10,6 +10,9 @@ const DATABASE: &str = "DATABASE";
 const TRUST_IDENTITY: &str = "trust-identity";
 const ADDRESS: &str = "address";
 const PORT: &str = "port";
+const MAX_REBASE_DISTANCE: &str = "max-rebase-distance";
+const MIN_TOC_HEADINGS: &str = "min-toc-headings";
+const ALLOWED_SCHEMES: &str = "allowed-schemes";
 
 fn args<'a>() -> clap::ArgMatches<'a> {
     use clap::{App, Arg};
@@ -55,6 +58,52 @@ fn args<'a>() -> clap::ArgMatches<'a> {
                 )
                 .long(TRUST_IDENTITY),
         )
+        .arg(
+            Arg::with_name(MAX_REBASE_DISTANCE)
+                .help(
+                    "Sets how many revisions behind the latest revision an edit \
+                can be and still be merged. Older edits are rejected.",
+                )
+                .long(MAX_REBASE_DISTANCE)
+                .default_value("100")
+                .validator(|x| match x.parse::<i32>() {
+                    Ok(x) if x >= 0 => Ok(()),
+                    _ => Err("Must be a non-negative integer".into()),
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(MIN_TOC_HEADINGS)
+                .help("Sets how many headings an article needs to get a table of contents")
+                .long(MIN_TOC_HEADINGS)
+                .default_value("4")
+                .validator(|x| match x.parse::<usize>() {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err("Must be a non-negative integer".into()),
+                })
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name(ALLOWED_SCHEMES)
+                .help(
+                    "Sets the URL schemes that links and images in articles can use. \
+                Relative URLs are always allowed.",
+                )
+                .long(ALLOWED_SCHEMES)
+                .default_value("http,https,ftp,mailto,tel")
+                .use_delimiter(true)
+                .validator(|x| {
+                    if !x.is_empty()
+                        && x.chars()
+                            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                    {
+                        Ok(())
+                    } else {
+                        Err("Must be a comma separated list of URL schemes".into())
+                    }
+                })
+                .takes_value(true),
+        )
         .get_matches()
 }
 
@@ -72,6 +121,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let bind_port = args.value_of(PORT).expect(CLAP).parse().expect(VALIDATOR);
 
     let trust_identity = args.is_present(TRUST_IDENTITY);
+    let max_rebase_distance = args
+        .value_of(MAX_REBASE_DISTANCE)
+        .expect(CLAP)
+        .parse()
+        .expect(VALIDATOR);
+    let min_toc_headings = args
+        .value_of(MIN_TOC_HEADINGS)
+        .expect(CLAP)
+        .parse()
+        .expect(VALIDATOR);
+    let allowed_schemes = args
+        .values_of(ALLOWED_SCHEMES)
+        .expect(CLAP)
+        .map(str::to_ascii_lowercase)
+        .collect();
 
-    sausagewiki::main(db_file, bind_host, bind_port, trust_identity)
+    sausagewiki::main(
+        db_file,
+        bind_host,
+        bind_port,
+        trust_identity,
+        max_rebase_distance,
+        min_toc_headings,
+        allowed_schemes,
+    )
 }
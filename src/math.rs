@@ -0,0 +1,457 @@
+// Converts the commonly used subset of TeX math to MathML, which browsers
+// render without any scripts or fonts from us. Unknown commands are shown
+// as errors rather than failing the whole formula.
+
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+fn escape_html(src: &str) -> String {
+    src.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn greek(name: &str) -> Option<char> {
+    Some(match name {
+        "alpha" => 'α',
+        "beta" => 'β',
+        "gamma" => 'γ',
+        "delta" => 'δ',
+        "epsilon" => 'ϵ',
+        "varepsilon" => 'ε',
+        "zeta" => 'ζ',
+        "eta" => 'η',
+        "theta" => 'θ',
+        "iota" => 'ι',
+        "kappa" => 'κ',
+        "lambda" => 'λ',
+        "mu" => 'μ',
+        "nu" => 'ν',
+        "xi" => 'ξ',
+        "pi" => 'π',
+        "rho" => 'ρ',
+        "sigma" => 'σ',
+        "tau" => 'τ',
+        "upsilon" => 'υ',
+        "phi" => 'ϕ',
+        "varphi" => 'φ',
+        "chi" => 'χ',
+        "psi" => 'ψ',
+        "omega" => 'ω',
+        "Gamma" => 'Γ',
+        "Delta" => 'Δ',
+        "Theta" => 'Θ',
+        "Lambda" => 'Λ',
+        "Xi" => 'Ξ',
+        "Pi" => 'Π',
+        "Sigma" => 'Σ',
+        "Phi" => 'Φ',
+        "Psi" => 'Ψ',
+        "Omega" => 'Ω',
+        _ => return None,
+    })
+}
+
+fn operator(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "cdot" => "⋅",
+        "times" => "×",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" | "neq" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "propto" => "∝",
+        "in" => "∈",
+        "notin" => "∉",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "cup" => "∪",
+        "cap" => "∩",
+        "land" | "wedge" => "∧",
+        "lor" | "vee" => "∨",
+        "neg" | "lnot" => "¬",
+        "forall" => "∀",
+        "exists" => "∃",
+        "to" | "rightarrow" => "→",
+        "leftarrow" => "←",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "emptyset" => "∅",
+        "ldots" | "dots" => "…",
+        "cdots" => "⋯",
+        "sum" => "∑",
+        "prod" => "∏",
+        "int" => "∫",
+        "oint" => "∮",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "mid" => "|",
+        "{" => "{",
+        "}" => "}",
+        "|" => "‖",
+        _ => return None,
+    })
+}
+
+fn function(name: &str) -> bool {
+    matches!(
+        name,
+        "sin"
+            | "cos"
+            | "tan"
+            | "log"
+            | "ln"
+            | "exp"
+            | "lim"
+            | "max"
+            | "min"
+            | "sup"
+            | "inf"
+            | "det"
+            | "gcd"
+            | "mod"
+    )
+}
+
+fn space(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "," => "0.167em",
+        ":" | ">" => "0.222em",
+        ";" => "0.278em",
+        "quad" => "1em",
+        "qquad" => "2em",
+        _ => return None,
+    })
+}
+
+// Groups nested deeper than this are an error, so the recursion cannot
+// overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Converter<'a> {
+    src: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    display: bool,
+    depth: usize,
+}
+
+impl<'a> Converter<'a> {
+    fn new(src: &'a str, display: bool) -> Converter<'a> {
+        Converter {
+            src,
+            chars: src.char_indices().peekable(),
+            display,
+            depth: 0,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn command_name(&mut self) -> &'a str {
+        let start = match self.chars.peek() {
+            Some(&(i, _)) => i,
+            None => return "",
+        };
+        let (_, first) = self.chars.next().expect("Peeked above");
+        if !first.is_ascii_alphabetic() {
+            return &self.src[start..start + first.len_utf8()];
+        }
+
+        let mut end = start + 1;
+        while let Some((i, _)) = self.chars.next_if(|&(_, c)| c.is_ascii_alphabetic()) {
+            end = i + 1;
+        }
+        &self.src[start..end]
+    }
+
+    // The raw text of a `{...}` argument, as for `\text{...}`
+    fn text_argument(&mut self) -> &'a str {
+        self.skip_whitespace();
+        if self.chars.next_if(|&(_, c)| c == '{').is_none() {
+            return "";
+        }
+        let start = self.chars.peek().map(|&(i, _)| i).unwrap_or(self.src.len());
+        let mut depth = 0;
+        for (i, c) in &mut self.chars {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return &self.src[start..i],
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        &self.src[start..]
+    }
+
+    fn delimiter(&mut self) -> String {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, '.')) | None => String::new(),
+            Some((_, '\\')) => match operator(self.command_name()) {
+                Some(op) => format!("<mo>{}</mo>", op),
+                None => String::new(),
+            },
+            Some((_, c)) => format!("<mo>{}</mo>", escape_html(&c.to_string())),
+        }
+    }
+
+    // A list of atoms with their sub- and superscripts, up to `}`,
+    // `\right` or the end
+    fn row(&mut self) -> String {
+        let mut buf = String::new();
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                None | Some((_, '}')) => break,
+                Some(&(i, '\\')) if self.src[i..].starts_with("\\right") => break,
+                _ => {}
+            }
+            buf.push_str(&self.scripted());
+        }
+        buf
+    }
+
+    fn scripted(&mut self) -> String {
+        let (base, big) = self.atom();
+
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.skip_whitespace();
+            match self.chars.peek() {
+                Some((_, '_')) if sub.is_none() => {
+                    self.chars.next();
+                    sub = Some(self.atom().0);
+                }
+                Some((_, '^')) if sup.is_none() => {
+                    self.chars.next();
+                    sup = Some(self.atom().0);
+                }
+                _ => break,
+            }
+        }
+
+        // Limits of sums and the like go above and below in display math
+        let (sub_tag, sup_tag, both_tag) = if big && self.display {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+
+        match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<{0}>{1}{2}</{0}>", sub_tag, base, sub),
+            (None, Some(sup)) => format!("<{0}>{1}{2}</{0}>", sup_tag, base, sup),
+            (Some(sub), Some(sup)) => format!("<{0}>{1}{2}{3}</{0}>", both_tag, base, sub, sup),
+        }
+    }
+
+    // A single element, and whether it is a big operator
+    fn atom(&mut self) -> (String, bool) {
+        if self.depth == MAX_DEPTH {
+            // The rest of the formula is skipped
+            self.chars.by_ref().for_each(drop);
+            return (
+                "<merror><mtext>Nested too deeply</mtext></merror>".to_owned(),
+                false,
+            );
+        }
+
+        self.depth += 1;
+        let atom = self.element();
+        self.depth -= 1;
+        atom
+    }
+
+    fn element(&mut self) -> (String, bool) {
+        self.skip_whitespace();
+        let c = match self.chars.next() {
+            Some((_, c)) => c,
+            None => return ("<mrow></mrow>".to_owned(), false),
+        };
+
+        let atom = match c {
+            '{' => {
+                let row = self.row();
+                self.chars.next_if(|&(_, c)| c == '}');
+                format!("<mrow>{}</mrow>", row)
+            }
+            '\\' => return self.command(),
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                while let Some((_, c)) =
+                    self.chars.next_if(|&(_, c)| c.is_ascii_digit() || c == '.')
+                {
+                    number.push(c);
+                }
+                format!("<mn>{}</mn>", number)
+            }
+            c if c.is_alphabetic() => format!("<mi>{}</mi>", c),
+            '\'' => "<mo>′</mo>".to_owned(),
+            '&' | '~' => "<mspace width=\"0.278em\"/>".to_owned(),
+            c => format!("<mo>{}</mo>", escape_html(&c.to_string())),
+        };
+
+        (atom, false)
+    }
+
+    fn command(&mut self) -> (String, bool) {
+        let name = self.command_name();
+
+        let atom = if let Some(c) = greek(name) {
+            format!("<mi>{}</mi>", c)
+        } else if let Some(op) = operator(name) {
+            let big = matches!(name, "sum" | "prod" | "int" | "oint");
+            return (format!("<mo>{}</mo>", op), big);
+        } else if function(name) {
+            return (format!("<mi>{}</mi>", name), name == "lim");
+        } else if let Some(width) = space(name) {
+            format!("<mspace width=\"{}\"/>", width)
+        } else {
+            match name {
+                "frac" => {
+                    let numerator = self.atom().0;
+                    let denominator = self.atom().0;
+                    format!("<mfrac>{}{}</mfrac>", numerator, denominator)
+                }
+                "sqrt" => {
+                    self.skip_whitespace();
+                    if self.chars.next_if(|&(_, c)| c == '[').is_some() {
+                        let mut index = String::new();
+                        while let Some((_, c)) = self.chars.next_if(|&(_, c)| c != ']') {
+                            index.push(c);
+                        }
+                        self.chars.next();
+                        let radicand = self.atom().0;
+                        let index = Converter {
+                            depth: self.depth,
+                            ..Converter::new(&index, false)
+                        }
+                        .row();
+                        format!("<mroot>{}<mrow>{}</mrow></mroot>", radicand, index)
+                    } else {
+                        format!("<msqrt>{}</msqrt>", self.atom().0)
+                    }
+                }
+                "text" | "mathrm" | "operatorname" => {
+                    format!("<mtext>{}</mtext>", escape_html(self.text_argument()))
+                }
+                "left" => {
+                    let open = self.delimiter();
+                    let row = self.row();
+                    // The row ends at `\right`, if not at the end
+                    let close = if self.chars.next_if(|&(_, c)| c == '\\').is_some() {
+                        self.command_name();
+                        self.delimiter()
+                    } else {
+                        String::new()
+                    };
+                    format!("<mrow>{}{}{}</mrow>", open, row, close)
+                }
+                "\\" => "<mspace linebreak=\"newline\"/>".to_owned(),
+                _ => format!("<merror><mtext>\\{}</mtext></merror>", escape_html(name)),
+            }
+        };
+
+        (atom, false)
+    }
+}
+
+pub fn to_mathml(tex: &str, display: bool) -> String {
+    let mut converter = Converter::new(tex, display);
+
+    let mut row = String::new();
+    while converter.chars.peek().is_some() {
+        row.push_str(&converter.row());
+        // Stray closing braces and `\right`s are skipped
+        if converter.chars.next_if(|&(_, c)| c == '}').is_none() {
+            converter.chars.next();
+            converter.command_name();
+        }
+    }
+
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"{}>\
+            <semantics><mrow>{}</mrow>\
+            <annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        if display { " display=\"block\"" } else { "" },
+        row,
+        escape_html(tex),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn body(tex: &str) -> String {
+        let mathml = to_mathml(tex, false);
+        let start = mathml.find("<semantics><mrow>").unwrap() + "<semantics><mrow>".len();
+        let end = mathml.find("</mrow><annotation").unwrap();
+        mathml[start..end].to_owned()
+    }
+
+    #[test]
+    fn scripts() {
+        assert_eq!(body("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            body("a_{i+1}^n"),
+            "<msubsup><mi>a</mi><mrow><mi>i</mi><mo>+</mo><mn>1</mn></mrow><mi>n</mi></msubsup>"
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            body("\\frac{1}{\\sqrt{n}} \\le \\alpha"),
+            "<mfrac><mrow><mn>1</mn></mrow><mrow><msqrt><mrow><mi>n</mi></mrow></msqrt></mrow></mfrac>\
+                <mo>≤</mo><mi>α</mi>"
+        );
+        assert_eq!(
+            body("\\left( x \\right) \\text{if } x<0"),
+            "<mrow><mo>(</mo><mi>x</mi><mo>)</mo></mrow><mtext>if </mtext>\
+                <mi>x</mi><mo>&lt;</mo><mn>0</mn>"
+        );
+        assert_eq!(body("\\foo"), "<merror><mtext>\\foo</mtext></merror>");
+    }
+
+    #[test]
+    fn display_limits() {
+        let mathml = to_mathml("\\sum_{i=0}^n i", true);
+        assert!(mathml
+            .starts_with("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">"));
+        assert!(mathml.contains("<munderover><mo>∑</mo>"));
+        assert!(mathml
+            .contains("<annotation encoding=\"application/x-tex\">\\sum_{i=0}^n i</annotation>"));
+    }
+
+    #[test]
+    fn unbalanced() {
+        assert_eq!(body("a}b"), "<mi>a</mi><mi>b</mi>");
+        assert_eq!(body("{a"), "<mrow><mi>a</mi></mrow>");
+    }
+
+    #[test]
+    fn nested_too_deeply() {
+        let error = "<merror><mtext>Nested too deeply</mtext></merror>";
+        assert!(body(&"{".repeat(100_000)).contains(error));
+        assert!(body(&"x^{".repeat(100_000)).contains(error));
+        assert!(body(&"\\sqrt[".repeat(100_000)).contains(error));
+        assert!(!body(&format!("{}x{}", "{".repeat(20), "}".repeat(20))).contains(error));
+    }
+}
@@ -25,6 +25,14 @@ pub struct ArticleRevision {
     pub author: Option<String>,
 
     pub theme: Theme,
+
+    pub deleted: bool,
+
+    pub restored_revision: Option<i32>,
+
+    pub summary: Option<String>,
+
+    pub minor: bool,
 }
 
 impl ArticleRevision {
@@ -49,6 +57,14 @@ pub struct ArticleRevisionStub {
     pub author: Option<String>,
 
     pub theme: Theme,
+
+    pub deleted: bool,
+
+    pub restored_revision: Option<i32>,
+
+    pub summary: Option<String>,
+
+    pub minor: bool,
 }
 
 impl ArticleRevisionStub {
@@ -75,3 +91,42 @@ impl SearchResult {
         slug_link(&self.slug)
     }
 }
+
+#[derive(Debug, QueryableByName)]
+pub struct WantedLink {
+    #[sql_type = "Text"]
+    pub target_slug: String,
+
+    #[sql_type = "Text"]
+    pub title: String,
+
+    #[sql_type = "Text"]
+    pub slug: String,
+}
+
+impl WantedLink {
+    pub fn link(&self) -> &str {
+        slug_link(&self.slug)
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+pub struct PropertyMatch {
+    #[sql_type = "Text"]
+    pub key: String,
+
+    #[sql_type = "Text"]
+    pub value: String,
+
+    #[sql_type = "Text"]
+    pub title: String,
+
+    #[sql_type = "Text"]
+    pub slug: String,
+}
+
+impl PropertyMatch {
+    pub fn link(&self) -> &str {
+        slug_link(&self.slug)
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Who a request is counted against: the trusted identity if there is one,
+// or else the address it came from
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Client {
+    Identity(String),
+    Address(IpAddr),
+    Unknown,
+}
+
+// Allows each client `requests` requests in every `period`
+pub struct RateLimit {
+    requests: u32,
+    period: Duration,
+    windows: Mutex<HashMap<Client, (Instant, u32)>>,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, period: Duration) -> RateLimit {
+        RateLimit {
+            requests,
+            period,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Counts a request, or returns how long to wait until the next one is
+    // allowed
+    pub fn check(&self, client: &Client) -> Result<(), Duration> {
+        self.check_at(client, Instant::now())
+    }
+
+    fn check_at(&self, client: &Client, now: Instant) -> Result<(), Duration> {
+        let mut windows = self.windows.lock().expect("Lock is never poisoned");
+
+        // Forget finished windows, so the map does not grow without bound
+        let period = self.period;
+        windows.retain(|_, &mut (start, _)| now.duration_since(start) < period);
+
+        let (start, count) = windows.entry(client.clone()).or_insert((now, 0));
+        if *count >= self.requests {
+            return Err(period - now.duration_since(*start));
+        }
+        *count += 1;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limits_each_client() {
+        let limit = RateLimit::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        let alice = Client::Identity("alice".to_owned());
+
+        assert_eq!(limit.check_at(&alice, start), Ok(()));
+        assert_eq!(limit.check_at(&alice, start), Ok(()));
+        assert_eq!(
+            limit.check_at(&alice, start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+
+        let bob = Client::Identity("bob".to_owned());
+        assert_eq!(limit.check_at(&bob, start), Ok(()));
+
+        let address = Client::Address("127.0.0.1".parse().unwrap());
+        assert_eq!(limit.check_at(&address, start), Ok(()));
+        assert_eq!(limit.check_at(&address, start), Ok(()));
+        assert!(limit.check_at(&address, start).is_err());
+        let other = Client::Address("::1".parse().unwrap());
+        assert_eq!(limit.check_at(&other, start), Ok(()));
+
+        assert_eq!(
+            limit.check_at(&alice, start + Duration::from_secs(60)),
+            Ok(())
+        );
+    }
+}
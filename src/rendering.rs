@@ -1,29 +1,1011 @@
-use pulldown_cmark::Event::{End, Text};
-use pulldown_cmark::{html, Parser, Tag, OPTION_DISABLE_HTML, OPTION_ENABLE_TABLES};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+
+use pulldown_cmark::Event::{End, Html, Start, Text};
+use pulldown_cmark::{
+    html, Event, Parser, Tag, OPTION_DISABLE_HTML, OPTION_ENABLE_FOOTNOTES, OPTION_ENABLE_TABLES,
+};
 use slug::slugify;
 
+use crate::highlight::highlight;
+use crate::math::to_mathml;
+
+// Rendered articles are cached by this version. build.rs hashes the sources
+// of the renderer, so any change to them refreshes the cache.
+pub const RENDERER_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "/", env!("RENDERER_HASH"));
+
+// How deeply `{{include:slug}}` directives can be nested, and how many are
+// expanded in total when rendering an article
+pub const MAX_INCLUDE_DEPTH: usize = 3;
+pub const MAX_INCLUDES: usize = 50;
+
+// The number of articles listed by macros such as `{{recent-changes}}`,
+// unless given with `n=`, and the most that can be asked for
+pub const DEFAULT_MACRO_LIMIT: i32 = 10;
+pub const MAX_MACRO_LIMIT: i32 = 100;
+
+// `{{children}}` lists the articles with this front matter property set to
+// the title or slug of the current article
+pub const PARENT_PROPERTY: &str = "parent";
+
+// A paragraph that consists of only `{{include:slug}}` or a macro such as
+// `{{recent-changes n=10}}` is replaced by its expansion
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Directive {
+    Include(String),
+    RecentChanges { limit: i32 },
+    ArticleList { tag: Option<String> },
+    Search { query: String, limit: i32 },
+    Children,
+}
+
+pub struct ListedArticle {
+    pub title: String,
+    pub link: String,
+    pub note: Option<String>,
+}
+
+pub enum Expansion {
+    Rendered(String),
+    Articles(Vec<ListedArticle>),
+    Missing,
+    Cycle,
+    TooDeep,
+    TooMany,
+}
+
+pub struct Heading {
+    pub level: i32,
+    pub id: String,
+    pub text: String,
+    pub section: usize,
+}
+
+// Shortcut links lead to the article with the slugified link text. The
+// text can also be `Target#Section` or `Target|label`, for wiki-style links.
 fn slugify_link(text: &str, title: &str) -> Option<(String, String)> {
-    Some((slugify(text), title.to_owned()))
+    let (target, title) = match (text.split_once('|'), title.split_once('|')) {
+        (Some((target, _)), Some((title, _))) => (target, title.trim()),
+        _ => (text, title),
+    };
+
+    let href = match target.split_once('#') {
+        Some((article, section)) => format!("{}#{}", slugify(article), slugify(section)),
+        None => slugify(target),
+    };
+
+    Some((href, title.to_owned()))
 }
 
-fn parser(src: &str) -> Parser {
-    let opts = OPTION_ENABLE_TABLES | OPTION_DISABLE_HTML;
+fn raw_parser(src: &str) -> Parser {
+    let opts = OPTION_ENABLE_TABLES | OPTION_ENABLE_FOOTNOTES | OPTION_DISABLE_HTML;
     Parser::new_with_broken_link_callback(src, opts, Some(&slugify_link))
 }
 
-pub fn render_markdown(src: &str) -> String {
-    let p = parser(src);
+// Math is kept out of the Markdown parser, so TeX such as `a_1 * b_1` or
+// `\{x\}` comes through unchanged. Each formula is replaced by a placeholder,
+// and `render_math` gives the event for it. The characters used by
+// placeholders are replaced in the rest of the text.
+fn parse(src: &str, render_math: fn(&str, bool) -> Event<'static>) -> Vec<Event> {
+    let math = find_math(src);
+
+    let (events, tasks) = if math.is_empty() {
+        (raw_parser(src).collect(), task_items(src))
+    } else {
+        let mut masked = String::new();
+        let mut formulas = vec![];
+        let mut rest = 0;
+        for (i, (range, display)) in math.into_iter().enumerate() {
+            let delimiter = if display { 2 } else { 1 };
+            formulas.push((
+                &src[range.start + delimiter..range.end - delimiter],
+                display,
+            ));
+            masked.push_str(&without_placeholders(&src[rest..range.start]));
+            masked.push_str(&format!("{}{}{}", MATH_START, i, MATH_END));
+            rest = range.end;
+        }
+        masked.push_str(&without_placeholders(&src[rest..]));
+
+        let events = raw_parser(&masked).map(owned_event).collect();
+        (
+            replace_math(events, &formulas, render_math),
+            task_items(&masked),
+        )
+    };
+
+    strikethrough(task_lists(wiki_links(events), &tasks))
+}
+
+fn parser(src: &str) -> impl Iterator<Item = Event> {
+    parse(src, |tex, display| Html(to_mathml(tex, display).into())).into_iter()
+}
+
+// Placeholders for math are made of characters from the private use area
+const MATH_START: char = '\u{E000}';
+const MATH_END: char = '\u{E001}';
+
+fn without_placeholders(src: &str) -> Cow<str> {
+    if src.contains([MATH_START, MATH_END]) {
+        src.replace([MATH_START, MATH_END], "\u{FFFD}").into()
+    } else {
+        src.into()
+    }
+}
+
+// The ranges of code spans and code blocks in `src`
+fn code_ranges(src: &str) -> Vec<Range<usize>> {
+    let mut p = raw_parser(src);
+    let mut ranges = vec![];
+    let mut start = None;
+    let mut offset = 0;
+
+    while let Some(event) = p.next() {
+        match event {
+            Start(Tag::Code) | Start(Tag::CodeBlock(_)) => start = start.or(Some(offset)),
+            End(Tag::Code) | End(Tag::CodeBlock(_)) => {
+                if let Some(start) = start.take() {
+                    ranges.push(start..p.get_offset());
+                }
+            }
+            _ => {}
+        }
+        offset = p.get_offset();
+    }
+
+    ranges
+}
+
+// The end of the math that starts with the delimiter at `start`. Math
+// cannot span a blank line.
+fn math_end(src: &str, start: usize, display: bool, code: &[Range<usize>]) -> Option<usize> {
+    let bytes = src.as_bytes();
+    let open = if display { start + 2 } else { start + 1 };
+
+    // `$ 5` is not math, but `$$ x $$` is
+    if !display && bytes.get(open).is_none_or(u8::is_ascii_whitespace) {
+        return None;
+    }
+
+    let mut i = open;
+    while i < bytes.len() {
+        if code.iter().any(|range| range.start == i) {
+            return None;
+        }
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n'
+                if src[i + 1..]
+                    .split('\n')
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .is_empty() =>
+            {
+                return None;
+            }
+            b'$' if display => {
+                if bytes.get(i + 1) == Some(&b'$') && !src[open..i].trim().is_empty() {
+                    return Some(i + 2);
+                }
+                i += 1;
+            }
+            b'$' => {
+                // `$x$5` is not closed, so amounts such as `$5 to $10` are
+                // not math
+                let closes = i > open
+                    && !bytes[i - 1].is_ascii_whitespace()
+                    && !bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+                return if closes { Some(i + 1) } else { None };
+            }
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+// `$inline$` and `$$display$$` math outside of code
+fn find_math(src: &str) -> Vec<(Range<usize>, bool)> {
+    if !src.contains('$') {
+        return vec![];
+    }
+
+    let code = code_ranges(src);
+    let bytes = src.as_bytes();
+    let mut math = vec![];
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(range) = code.iter().find(|range| range.contains(&i)) {
+            i = range.end;
+            continue;
+        }
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'$' => {
+                let display = bytes.get(i + 1) == Some(&b'$');
+                match math_end(src, i, display, &code) {
+                    Some(end) => {
+                        math.push((i..end, display));
+                        i = end;
+                    }
+                    None if display => i += 2,
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    math
+}
+
+fn owned_str<'a>(x: Cow<str>) -> Cow<'a, str> {
+    Cow::Owned(x.into_owned())
+}
+
+fn owned_tag<'a>(tag: Tag) -> Tag<'a> {
+    match tag {
+        Tag::Paragraph => Tag::Paragraph,
+        Tag::Rule => Tag::Rule,
+        Tag::Header(level) => Tag::Header(level),
+        Tag::BlockQuote => Tag::BlockQuote,
+        Tag::CodeBlock(info) => Tag::CodeBlock(owned_str(info)),
+        Tag::List(start) => Tag::List(start),
+        Tag::Item => Tag::Item,
+        Tag::FootnoteDefinition(name) => Tag::FootnoteDefinition(owned_str(name)),
+        Tag::Table(alignments) => Tag::Table(alignments),
+        Tag::TableHead => Tag::TableHead,
+        Tag::TableRow => Tag::TableRow,
+        Tag::TableCell => Tag::TableCell,
+        Tag::Emphasis => Tag::Emphasis,
+        Tag::Strong => Tag::Strong,
+        Tag::Code => Tag::Code,
+        Tag::Link(uri, title) => Tag::Link(owned_str(uri), owned_str(title)),
+        Tag::Image(uri, title) => Tag::Image(owned_str(uri), owned_str(title)),
+    }
+}
+
+fn owned_event<'a>(event: Event) -> Event<'a> {
+    match event {
+        Start(tag) => Start(owned_tag(tag)),
+        End(tag) => End(owned_tag(tag)),
+        Text(text) => Text(owned_str(text)),
+        Html(html) => Html(owned_str(html)),
+        Event::InlineHtml(html) => Event::InlineHtml(owned_str(html)),
+        Event::FootnoteReference(name) => Event::FootnoteReference(owned_str(name)),
+        Event::SoftBreak => Event::SoftBreak,
+        Event::HardBreak => Event::HardBreak,
+    }
+}
+
+fn replace_math<'a>(
+    events: Vec<Event<'a>>,
+    formulas: &[(&str, bool)],
+    render_math: fn(&str, bool) -> Event<'static>,
+) -> Vec<Event<'a>> {
+    let mut result = Vec::with_capacity(events.len());
+
+    for event in events {
+        let mut text = match event {
+            Text(ref text) if text.contains(MATH_START) => &text[..],
+            _ => {
+                result.push(event);
+                continue;
+            }
+        };
+
+        while let Some((before, rest)) = text.split_once(MATH_START) {
+            let formula = rest.split_once(MATH_END).and_then(|(index, rest)| {
+                Some((formulas.get(index.parse::<usize>().ok()?)?, rest))
+            });
+            let (&(tex, display), rest) = match formula {
+                Some(x) => x,
+                None => break,
+            };
+
+            if !before.is_empty() {
+                result.push(Text(before.to_owned().into()));
+            }
+            result.push(render_math(tex, display));
+            text = rest;
+        }
+        if !text.is_empty() {
+            result.push(Text(text.to_owned().into()));
+        }
+    }
+
+    result
+}
+
+// The index of the end of the link starting at `start`, if the link is
+// enclosed in brackets
+fn bracketed_link_end(events: &[Event], start: usize) -> Option<usize> {
+    match (&events[start - 1], &events[start]) {
+        (Text(before), Start(Tag::Link(..))) if before.ends_with('[') => {}
+        _ => return None,
+    }
+
+    let end = start
+        + events[start..]
+            .iter()
+            .position(|x| matches!(x, End(Tag::Link(..))))?;
+
+    match events.get(end + 1) {
+        Some(Text(after)) if after.starts_with(']') => Some(end),
+        _ => None,
+    }
+}
+
+// Whether every tag started in `events` also ends in them
+fn is_balanced(events: &[Event]) -> bool {
+    let mut depth = 0usize;
+    for event in events {
+        match event {
+            Start(_) => depth += 1,
+            End(_) => match depth.checked_sub(1) {
+                Some(x) => depth = x,
+                None => return false,
+            },
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+// `[[Target|label]]` parses as a shortcut link in brackets. Remove the
+// brackets and show only the label. When the `|` is inside formatting, as
+// in `[[*A|b*]]`, the whole text is shown instead.
+fn wiki_links(mut events: Vec<Event>) -> Vec<Event> {
+    let mut i = 1;
+    while i + 1 < events.len() {
+        if let Some(end) = bracketed_link_end(&events, i) {
+            if let Text(before) = &mut events[i - 1] {
+                *before = before[..before.len() - 1].to_owned().into();
+            }
+            if let Text(after) = &mut events[end + 1] {
+                *after = after[1..].to_owned().into();
+            }
+
+            let label_start = events[i + 1..end]
+                .iter()
+                .position(|x| match x {
+                    Text(text) => text.contains('|'),
+                    _ => false,
+                })
+                .map(|x| i + 1 + x)
+                .filter(|&x| is_balanced(&events[i + 1..x]));
+            if let Some(label_start) = label_start {
+                if let Text(text) = &mut events[label_start] {
+                    let (_, label) = text.split_once('|').expect("Text contains a '|'");
+                    *text = label.trim_start().to_owned().into();
+                }
+                events.drain(i + 1..label_start);
+            }
+        }
+
+        i += 1;
+    }
+
+    events
+}
+
+// `- [ ] Task` and `- [x] Done` list items are tasks. This gives, for each
+// list item in `src`, whether it is a task and if so whether it is done.
+fn task_items(src: &str) -> Vec<Option<bool>> {
+    let mut p = raw_parser(src);
+    let mut items = vec![];
+    let mut in_item_start = false;
+    let mut offset = 0;
+
+    while let Some(event) = p.next() {
+        match event {
+            Start(Tag::Item) => {
+                items.push(None);
+                in_item_start = true;
+            }
+            Start(Tag::Paragraph) if in_item_start => {}
+            _ if in_item_start => {
+                in_item_start = false;
+                let text = src.get(offset..).unwrap_or("");
+                let checked = match text.get(..3) {
+                    Some("[ ]") => Some(false),
+                    Some("[x]") | Some("[X]") => Some(true),
+                    _ => None,
+                };
+                if checked.is_some() && text.get(3..).is_some_and(|x| x.starts_with([' ', '\t'])) {
+                    *items.last_mut().expect("An item was started") = checked;
+                }
+            }
+            _ => {}
+        }
+        offset = p.get_offset();
+    }
+
+    items
+}
+
+// Replaces the `[ ]` or `[x]` of the items that are tasks, as found by
+// `task_items`, with a checkbox. The brackets parse either as text or as a
+// shortcut link.
+fn task_lists<'a>(events: Vec<Event<'a>>, tasks: &[Option<bool>]) -> Vec<Event<'a>> {
+    if tasks.iter().all(Option::is_none) {
+        return events;
+    }
+
+    let mut result = Vec::with_capacity(events.len());
+    let mut tasks = tasks.iter();
+    let mut events = events.into_iter().peekable();
+
+    while let Some(event) = events.next() {
+        let checked = match event {
+            Start(Tag::Item) => tasks.next().copied().flatten(),
+            _ => None,
+        };
+        let checked = match checked {
+            Some(checked) => checked,
+            None => {
+                result.push(event);
+                continue;
+            }
+        };
+
+        result.push(Html("<li class=\"task\">".into()));
+        if let Some(Start(Tag::Paragraph)) = events.peek() {
+            result.extend(events.next());
+        }
+        result.push(Html(
+            if checked {
+                "<input type=\"checkbox\" disabled checked>"
+            } else {
+                "<input type=\"checkbox\" disabled>"
+            }
+            .into(),
+        ));
+
+        match events.next() {
+            Some(Start(Tag::Link(..))) => {
+                for event in events.by_ref() {
+                    if let End(Tag::Link(..)) = event {
+                        break;
+                    }
+                }
+            }
+            Some(Text(text)) => {
+                if let Some(rest) = text.get(3..).filter(|x| !x.is_empty()) {
+                    result.push(Text(rest.to_owned().into()));
+                }
+            }
+            other => result.extend(other),
+        }
+    }
+
+    result
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis | Tag::Strong | Tag::Code | Tag::Link(..) | Tag::Image(..)
+    )
+}
+
+// `~~text~~` is struck out. The markers pair up within the same block and
+// the same inline element, so `**~~a** b~~` is left as it is. Markers in
+// code are left alone.
+fn strikethrough(events: Vec<Event>) -> Vec<Event> {
+    // The event and offset of each paired marker, and whether it opens
+    let mut markers: Vec<(usize, usize, bool)> = vec![];
+    // The unpaired opening markers of the block and of each inline element
+    // in it, innermost last
+    let mut unpaired: Vec<Vec<(usize, usize)>> = vec![vec![]];
+    let mut in_code = false;
+
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Start(Tag::Code) | Start(Tag::CodeBlock(_)) => in_code = true,
+            End(Tag::Code) | End(Tag::CodeBlock(_)) => in_code = false,
+            _ => {}
+        }
+
+        // Unpaired markers are left as they are
+        match event {
+            Start(tag) if is_inline(tag) => unpaired.push(vec![]),
+            End(tag) if is_inline(tag) && unpaired.len() > 1 => {
+                unpaired.pop();
+            }
+            Start(_) | End(_) => unpaired = vec![vec![]],
+            Text(text) if !in_code => {
+                let innermost = unpaired.last_mut().expect("The block is always there");
+                for (offset, _) in text.match_indices("~~") {
+                    match innermost.pop() {
+                        Some((opening, opening_offset)) => {
+                            markers.push((opening, opening_offset, true));
+                            markers.push((i, offset, false));
+                        }
+                        None => innermost.push((i, offset)),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if markers.is_empty() {
+        return events;
+    }
+    markers.sort_unstable();
+
+    let mut result = Vec::with_capacity(events.len() + markers.len() * 2);
+    let mut markers = markers.into_iter().peekable();
+
+    for (i, event) in events.into_iter().enumerate() {
+        let text = match event {
+            Text(ref text) if markers.peek().map(|&(x, _, _)| x) == Some(i) => text,
+            _ => {
+                result.push(event);
+                continue;
+            }
+        };
+
+        let mut rest = 0;
+        while let Some((_, offset, opens)) = markers.next_if(|&(x, _, _)| x == i) {
+            if offset > rest {
+                result.push(Text(text[rest..offset].to_owned().into()));
+            }
+            result.push(Html(if opens { "<del>" } else { "</del>" }.into()));
+            rest = offset + 2;
+        }
+        if rest < text.len() {
+            result.push(Text(text[rest..].to_owned().into()));
+        }
+    }
+
+    result
+}
+
+fn escape_html(src: &str) -> String {
+    src.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Links to the slugs in `missing` are marked as leading to articles that do
+// not exist, like red links in MediaWiki. `expansions` holds the expansion of
+// each directive in `src`. Links and images can only use the URL schemes in
+// `allowed_schemes`, while relative URLs are always allowed.
+pub fn render_markdown(
+    src: &str,
+    missing: &HashSet<String>,
+    expansions: &HashMap<Directive, Expansion>,
+    allowed_schemes: &[String],
+) -> String {
+    let mut buf = String::new();
+
+    let properties = properties(src);
+    if !properties.is_empty() {
+        buf.push_str("<table class=\"infobox\">\n");
+        for (key, value) in &properties {
+            let link = serde_urlencoded::to_string([("key", key), ("value", value)])
+                .expect("Serializing to String cannot fail");
+            buf.push_str(&format!(
+                "<tr><th>{}</th><td><a href=\"_properties?{}\">{}</a></td></tr>\n",
+                escape_html(key),
+                escape_html(&link),
+                escape_html(value),
+            ));
+        }
+        buf.push_str("</table>\n");
+    }
+
+    buf.push_str(&render_body(src, missing, expansions, allowed_schemes));
+
+    let tags = tags(src);
+    if !tags.is_empty() {
+        buf.push_str("<p class=\"tags\">Tags: ");
+        for (i, tag) in tags.iter().enumerate() {
+            if i != 0 {
+                buf.push_str(", ");
+            }
+            buf.push_str(&format!("<a href=\"_tags/{0}\">{0}</a>", tag));
+        }
+        buf.push_str("</p>\n");
+    }
+
+    buf
+}
+
+// The article without its front matter, which is also how it appears when
+// included in another article
+pub fn render_body(
+    src: &str,
+    missing: &HashSet<String>,
+    expansions: &HashMap<Directive, Expansion>,
+    allowed_schemes: &[String],
+) -> String {
+    let (_, body) = split_front_matter(src);
     let mut buf = String::new();
+
+    let events = sanitize_links(parser(body).collect(), allowed_schemes)
+        .into_iter()
+        .map(|event| match event {
+        Start(Tag::Link(ref uri, ref title)) => match link_target_slug(uri) {
+            Some(ref slug) if missing.contains(slug) => Html(
+                format!(
+                    "<a href=\"{}\" class=\"missing-article\" title=\"{} (article does not exist)\">",
+                    escape_html(uri),
+                    escape_html(if title.is_empty() { slug } else { title }),
+                )
+                .into(),
+            ),
+            None if is_external_url(uri) => Html(
+                format!(
+                    "<a href=\"{}\"{} class=\"external-link\" rel=\"nofollow noopener\">",
+                    escape_html(uri),
+                    if title.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" title=\"{}\"", escape_html(title))
+                    },
+                )
+                .into(),
+            ),
+            _ => event,
+        },
+        _ => event,
+    })
+    .collect::<Vec<_>>();
+    let events = replace_directives(events, expansions);
+    let events = highlight_code_blocks(events);
+
+    let mut ids = headings(&events).into_iter().map(|heading| heading.id);
+    let p = events.into_iter().map(|event| match event {
+        Start(Tag::Header(level)) => Html(
+            format!(
+                "<h{} id=\"{}\">",
+                level,
+                ids.next().expect("There is an id for each heading")
+            )
+            .into(),
+        ),
+        _ => event,
+    });
     html::push_html(&mut buf, p);
+
     buf
 }
 
+// `key=value` arguments, where values with spaces can be quoted
+fn directive_arguments(mut src: &str) -> Option<HashMap<&str, &str>> {
+    let mut arguments = HashMap::new();
+
+    loop {
+        src = src.trim_start();
+        if src.is_empty() {
+            break Some(arguments);
+        }
+
+        let (key, rest) = src.split_once('=')?;
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"')?,
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+
+        arguments.insert(key, value);
+        src = rest;
+    }
+}
+
+fn macro_limit(arguments: &HashMap<&str, &str>) -> Option<i32> {
+    match arguments.get("n") {
+        None => Some(DEFAULT_MACRO_LIMIT),
+        Some(n) => n.parse().ok().filter(|n| (1..=MAX_MACRO_LIMIT).contains(n)),
+    }
+}
+
+fn parse_directive(text: &str) -> Option<Directive> {
+    let inner = text.trim().strip_prefix("{{")?.strip_suffix("}}")?.trim();
+
+    if let Some(target) = inner.strip_prefix("include:") {
+        let target = target.trim();
+        if target.is_empty() {
+            return None;
+        }
+        return Some(Directive::Include(slugify(target)));
+    }
+
+    let (name, arguments) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+    let arguments = directive_arguments(arguments)?;
+
+    let (directive, known) = match name {
+        "recent-changes" => (
+            Directive::RecentChanges {
+                limit: macro_limit(&arguments)?,
+            },
+            &["n"][..],
+        ),
+        "article-list" => (
+            Directive::ArticleList {
+                tag: arguments.get("tag").map(|&tag| slugify(tag)),
+            },
+            &["tag"][..],
+        ),
+        "search" => (
+            Directive::Search {
+                query: arguments.get("q")?.to_string(),
+                limit: macro_limit(&arguments)?,
+            },
+            &["q", "n"][..],
+        ),
+        "children" => (Directive::Children, &[][..]),
+        _ => return None,
+    };
+
+    if arguments.keys().any(|key| !known.contains(key)) {
+        return None;
+    }
+
+    Some(directive)
+}
+
+// A directive must be a paragraph by itself. Returns the directive and the
+// index of the end of the paragraph.
+fn directive_at(events: &[Event], start: usize) -> Option<(Directive, usize)> {
+    if !matches!(events[start], Start(Tag::Paragraph)) {
+        return None;
+    }
+
+    let mut text = String::new();
+    for (i, event) in events.iter().enumerate().skip(start + 1) {
+        match event {
+            Text(x) => text.push_str(x),
+            End(Tag::Paragraph) => return parse_directive(&text).map(|directive| (directive, i)),
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn include_error(slug: &str, expansion: Option<&Expansion>) -> String {
+    let error = match expansion {
+        Some(Expansion::Cycle) => "the article includes itself",
+        Some(Expansion::TooDeep) => "includes are nested too deeply",
+        Some(Expansion::TooMany) => "too many articles are included",
+        _ => "the article does not exist",
+    };
+
+    format!(
+        "<p class=\"include-error\">Cannot include <a href=\"{0}\">{0}</a>: {1}</p>\n",
+        escape_html(slug),
+        error,
+    )
+}
+
+fn render_expansion(directive: &Directive, expansion: Option<&Expansion>) -> String {
+    let articles = match (directive, expansion) {
+        (_, Some(Expansion::Rendered(html))) => {
+            return format!("<div class=\"included\">\n{}</div>\n", html);
+        }
+        (_, Some(Expansion::Articles(articles))) => &articles[..],
+        (Directive::Include(slug), _) => return include_error(slug, expansion),
+        _ => &[],
+    };
+
+    if articles.is_empty() {
+        return "<p class=\"macro-list\">No articles</p>\n".to_owned();
+    }
+
+    let mut buf = "<ul class=\"macro-list\">\n".to_owned();
+    for article in articles {
+        buf.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            escape_html(&article.link),
+            escape_html(&article.title),
+        ));
+        if let Some(note) = &article.note {
+            buf.push_str(&format!(
+                " <span class=\"note\">{}</span>",
+                escape_html(note)
+            ));
+        }
+        buf.push_str("</li>\n");
+    }
+    buf.push_str("</ul>\n");
+
+    buf
+}
+
+// Fenced code blocks are highlighted by their language tag, when it is one
+// the highlighter knows
+fn highlight_code_blocks(events: Vec<Event>) -> Vec<Event> {
+    let mut result = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+
+    while let Some(event) = events.next() {
+        let lang = match event {
+            Start(Tag::CodeBlock(ref info)) => info.split(' ').next().unwrap_or("").to_owned(),
+            _ => {
+                result.push(event);
+                continue;
+            }
+        };
+
+        let mut code = String::new();
+        let mut end = None;
+        for inner in &mut events {
+            match inner {
+                Text(text) => code.push_str(&text),
+                End(_) => {
+                    end = Some(inner);
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        match highlight(&lang, &code) {
+            Some(highlighted) => result.push(Html(
+                format!(
+                    "<pre><code class=\"language-{}\">{}</code></pre>\n",
+                    escape_html(&lang),
+                    highlighted
+                )
+                .into(),
+            )),
+            None => {
+                result.push(event);
+                result.push(Text(code.into()));
+                result.extend(end);
+            }
+        }
+    }
+
+    result
+}
+
+fn replace_directives<'a>(
+    events: Vec<Event<'a>>,
+    expansions: &HashMap<Directive, Expansion>,
+) -> Vec<Event<'a>> {
+    let mut result = Vec::with_capacity(events.len());
+    let mut i = 0;
+    while i < events.len() {
+        if let Some((directive, end)) = directive_at(&events, i) {
+            let expansion = expansions.get(&directive);
+            result.push(Html(render_expansion(&directive, expansion).into()));
+            i = end + 1;
+        } else {
+            result.push(events[i].clone());
+            i += 1;
+        }
+    }
+
+    result
+}
+
+// Headings get ids from their text, so links to them stay valid across
+// unrelated edits. Repeated headings are disambiguated with a number.
+fn headings(events: &[Event]) -> Vec<Heading> {
+    let mut used = HashSet::new();
+    let mut headings = vec![];
+    let mut current: Option<(i32, String)> = None;
+
+    for event in events {
+        match *event {
+            Start(Tag::Header(level)) => current = Some((level, String::new())),
+            Text(ref text) => {
+                if let Some((_, ref mut buf)) = current {
+                    buf.push_str(text);
+                }
+            }
+            End(Tag::Header(_)) => {
+                if let Some((level, text)) = current.take() {
+                    let base_id = match slugify(&text) {
+                        ref x if x.is_empty() => "section".to_owned(),
+                        x => x,
+                    };
+
+                    let mut id = base_id.clone();
+                    let mut disambiguator = 1;
+                    while !used.insert(id.clone()) {
+                        disambiguator += 1;
+                        id = format!("{}-{}", base_id, disambiguator);
+                    }
+
+                    headings.push(Heading {
+                        level,
+                        id,
+                        text,
+                        section: headings.len() + 1,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+// Articles with at least `min_headings` headings get a table of contents
+pub fn table_of_contents(src: &str, min_headings: usize) -> Vec<Heading> {
+    let (_, body) = split_front_matter(src);
+    let headings = headings(&parser(body).collect::<Vec<_>>());
+
+    if headings.len() >= min_headings {
+        headings
+    } else {
+        vec![]
+    }
+}
+
+// The byte offsets of the lines where the headings start, with their levels
+fn heading_starts(src: &str) -> Vec<(i32, usize)> {
+    let (_, body) = split_front_matter(src);
+    let base = src.len() - body.len();
+
+    let mut p = raw_parser(body);
+    let mut starts = vec![];
+    while let Some(event) = p.next() {
+        if let Start(Tag::Header(level)) = event {
+            // After the start of a heading, the parser is somewhere on the
+            // first line of the heading
+            let offset = p.get_offset().min(body.len());
+            let line_start = body[..offset].rfind('\n').map_or(0, |x| x + 1);
+            starts.push((level, base + line_start));
+        }
+    }
+
+    starts
+}
+
+// Section `n` counts from 1 and spans from the `n`th heading to the next
+// heading of the same or a higher level
+fn section_range(src: &str, n: usize) -> Option<Range<usize>> {
+    let starts = heading_starts(src);
+    let (level, start) = *starts.get(n.checked_sub(1)?)?;
+    let end = starts[n..]
+        .iter()
+        .find(|&&(x, _)| x <= level)
+        .map_or(src.len(), |&(_, x)| x);
+
+    Some(start..end)
+}
+
+pub fn section(src: &str, n: usize) -> Option<&str> {
+    section_range(src, n).map(|range| &src[range])
+}
+
+pub fn replace_section(src: &str, n: usize, text: &str) -> Option<String> {
+    let range = section_range(src, n)?;
+
+    let mut buf = String::with_capacity(src.len() + text.len());
+    buf.push_str(&src[..range.start]);
+    if range.end < src.len() {
+        // Keep the following heading apart from the section
+        let newline = if src.contains("\r\n") { "\r\n" } else { "\n" };
+        buf.push_str(text.trim_end_matches(['\r', '\n']));
+        buf.push_str(newline);
+        buf.push_str(newline);
+    } else {
+        buf.push_str(text);
+    }
+    buf.push_str(&src[range.end..]);
+
+    Some(buf)
+}
+
 fn is_html_special(c: char) -> bool {
     c == '&' || c == '<' || c == '>'
 }
 
+// Math is indexed as its TeX source
 pub fn render_markdown_for_fts(src: &str) -> String {
-    let p = parser(src);
+    let p = parse(src, |tex, _display| Text(tex.to_owned().into()));
     let mut buf = String::new();
 
     for event in p {
@@ -41,21 +1023,763 @@ pub fn render_markdown_for_fts(src: &str) -> String {
     buf
 }
 
+// The article as plain text, for reading without a Markdown viewer. Links
+// are followed by their URL, and math is left as TeX.
+pub fn render_markdown_as_text(title: &str, src: &str) -> String {
+    fn end_line(buf: &mut String) {
+        if !buf.ends_with('\n') {
+            buf.push('\n');
+        }
+    }
+
+    fn end_block(buf: &mut String) {
+        let len = buf.trim_end().len();
+        buf.truncate(len);
+        buf.push_str("\n\n");
+    }
+
+    // `lists` holds the next number of each ordered list, and `None` for
+    // unordered lists
+    fn start_item(buf: &mut String, lists: &mut [Option<usize>]) {
+        end_line(buf);
+        buf.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+        match lists.last_mut() {
+            Some(Some(n)) => {
+                buf.push_str(&format!("{}. ", n));
+                *n += 1;
+            }
+            _ => buf.push_str("- "),
+        }
+    }
+
+    let (_, body) = split_front_matter(src);
+    let mut buf = format!("{}\n{}\n\n", title, "=".repeat(title.chars().count()));
+    let mut lists = vec![];
+    let mut cell_start = 0;
+
+    for event in parse(body, |tex, _display| Text(tex.to_owned().into())) {
+        match event {
+            Text(text) => buf.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => buf.push('\n'),
+            Event::FootnoteReference(name) => buf.push_str(&format!("[{}]", name)),
+            Start(Tag::FootnoteDefinition(name)) => buf.push_str(&format!("[{}]: ", name)),
+            Start(Tag::List(start)) => {
+                end_line(&mut buf);
+                lists.push(start);
+            }
+            End(Tag::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    end_block(&mut buf);
+                }
+            }
+            Start(Tag::Item) => start_item(&mut buf, &mut lists),
+            End(Tag::Item) => end_line(&mut buf),
+            // Task list items, from `task_lists`
+            Html(html) => match html.as_ref() {
+                "<li class=\"task\">" => start_item(&mut buf, &mut lists),
+                "<input type=\"checkbox\" disabled>" => buf.push_str("[ ]"),
+                "<input type=\"checkbox\" disabled checked>" => buf.push_str("[x]"),
+                _ => {}
+            },
+            Start(Tag::TableCell) => cell_start = buf.len(),
+            End(Tag::TableCell) => {
+                let cell = buf.split_off(cell_start);
+                buf.push_str(cell.trim());
+                buf.push('\t');
+            }
+            End(Tag::TableHead) | End(Tag::TableRow) => {
+                buf.pop();
+                buf.push('\n');
+            }
+            End(Tag::Link(uri, _)) | End(Tag::Image(uri, _)) => {
+                buf.push_str(&format!(" <{}>", uri))
+            }
+            Start(Tag::Rule) => buf.push_str("----"),
+            End(Tag::Paragraph) if !lists.is_empty() => end_line(&mut buf),
+            End(Tag::Paragraph)
+            | End(Tag::Header(_))
+            | End(Tag::Rule)
+            | End(Tag::CodeBlock(_))
+            | End(Tag::Table(_)) => end_block(&mut buf),
+            _ => {}
+        }
+    }
+
+    let len = buf.trim_end().len();
+    buf.truncate(len);
+    buf.push('\n');
+    buf
+}
+
+fn url_scheme(url: &str) -> Option<String> {
+    // Browsers ignore these characters, so `java\tscript:` is a `javascript:`
+    // URL
+    let url = url
+        .trim_matches(|c: char| c <= ' ')
+        .replace(['\t', '\n', '\r'], "");
+
+    let end = url.find([':', '/', '?', '#'])?;
+    if url[end..].starts_with(':') {
+        Some(url[..end].to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+fn is_safe_url(url: &str, allowed_schemes: &[String]) -> bool {
+    match url_scheme(url) {
+        Some(scheme) => allowed_schemes.contains(&scheme),
+        None => true,
+    }
+}
+
+fn is_external_url(url: &str) -> bool {
+    match url_scheme(url) {
+        Some(scheme) => matches!(scheme.as_str(), "http" | "https" | "ftp"),
+        None => url.starts_with("//"),
+    }
+}
+
+// Links and images with URL schemes that are not allowed, such as
+// `javascript:` or `data:`, are reduced to their text
+fn sanitize_links<'a>(events: Vec<Event<'a>>, allowed_schemes: &[String]) -> Vec<Event<'a>> {
+    let mut dropped = vec![];
+
+    events
+        .into_iter()
+        .filter(|event| match event {
+            Start(Tag::Link(uri, _)) | Start(Tag::Image(uri, _)) => {
+                let safe = is_safe_url(uri, allowed_schemes);
+                dropped.push(!safe);
+                safe
+            }
+            End(Tag::Link(..)) | End(Tag::Image(..)) => !dropped.pop().unwrap_or(false),
+            _ => true,
+        })
+        .collect()
+}
+
+fn link_target_slug(uri: &str) -> Option<String> {
+    let target = uri.split(['#', '?']).next().unwrap_or("");
+
+    // Only plain slugs link to other articles. Anything else is an external
+    // link, a reserved path or a link to the front page.
+    if target.is_empty() || target.starts_with('_') || target.starts_with('.') {
+        return None;
+    }
+    if target.contains([':', '/']) {
+        return None;
+    }
+
+    Some(slugify(target))
+}
+
+pub fn link_targets(src: &str) -> Vec<String> {
+    parser(src)
+        .filter_map(|event| match event {
+            Start(Tag::Link(uri, _title)) => link_target_slug(&uri),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+pub fn directives(src: &str) -> Vec<Directive> {
+    let (_, body) = split_front_matter(src);
+    let events = parser(body).collect::<Vec<_>>();
+
+    (0..events.len())
+        .filter_map(|i| directive_at(&events, i))
+        .map(|(directive, _)| directive)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+pub fn markdown_to_links(src: &str) -> String {
+    let mut buf = String::new();
+
+    for target in link_targets(src) {
+        buf.push_str(&target);
+        buf.push('\n');
+    }
+
+    buf
+}
+
+// Front matter is a block of `key: value` lines between two `---` lines, or
+// `key = value` lines between two `+++` lines, at the very start of the
+// article. It is not part of the rendered article.
+pub fn split_front_matter(src: &str) -> (Option<&str>, &str) {
+    let (delimiter, rest) = match ["---", "+++"].iter().find_map(|&delimiter| {
+        let rest = src.strip_prefix(delimiter)?;
+        let rest = rest
+            .strip_prefix('\n')
+            .or_else(|| rest.strip_prefix("\r\n"))?;
+        Some((delimiter, rest))
+    }) {
+        Some(x) => x,
+        None => return (None, src),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    (None, src)
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for quote in &['"', '\''] {
+        if let Some(x) = value
+            .strip_prefix(*quote)
+            .and_then(|x| x.strip_suffix(*quote))
+        {
+            return x;
+        }
+    }
+    value
+}
+
+// The key/value pairs of the front matter. Keys are lowercased. Nested
+// structures are not supported, so lines that are not plain key/value pairs
+// are ignored.
+fn front_matter_entries(src: &str) -> Vec<(String, &str)> {
+    let front_matter = match split_front_matter(src) {
+        (Some(front_matter), _) => front_matter,
+        (None, _) => return vec![],
+    };
+
+    front_matter
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let split = line.find([':', '='])?;
+            let key = line[..split].trim().to_lowercase();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return None;
+            }
+            Some((key, unquote(&line[split + 1..])))
+        })
+        .collect()
+}
+
+// Tags are given in the front matter as `tags: first, second`, optionally
+// in brackets, and are normalized to slugs
+pub fn tags(src: &str) -> Vec<String> {
+    front_matter_entries(src)
+        .into_iter()
+        .filter(|(key, _)| key == "tags")
+        .flat_map(|(_, value)| {
+            let value = value
+                .strip_prefix('[')
+                .and_then(|x| x.strip_suffix(']'))
+                .unwrap_or(value);
+            value.split(',').map(|tag| slugify(unquote(tag)))
+        })
+        .filter(|tag| !tag.is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+// All the other front matter entries are article properties. When a key is
+// repeated, the last value is used.
+pub fn properties(src: &str) -> Vec<(String, String)> {
+    let mut properties: Vec<(String, String)> = vec![];
+
+    for (key, value) in front_matter_entries(src) {
+        if key == "tags" {
+            continue;
+        }
+        properties.retain(|(x, _)| *x != key);
+        if !value.is_empty() {
+            properties.push((key, value.to_owned()));
+        }
+    }
+
+    properties
+}
+
+pub fn markdown_to_tags(src: &str) -> String {
+    let mut buf = String::new();
+
+    for tag in tags(src) {
+        buf.push_str(&tag);
+        buf.push('\n');
+    }
+
+    buf
+}
+
+// Each property is given as the key and value separated by a tab
+pub fn markdown_to_properties(src: &str) -> String {
+    let mut buf = String::new();
+
+    for (key, value) in properties(src) {
+        buf.push_str(&key);
+        buf.push('\t');
+        buf.push_str(&value);
+        buf.push('\n');
+    }
+
+    buf
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn allowed_schemes() -> Vec<String> {
+        ["http", "https", "ftp", "mailto", "tel"]
+            .iter()
+            .map(|&x| x.to_owned())
+            .collect()
+    }
+
     #[test]
     fn slug_link() {
-        let actual = render_markdown("[Slug link]");
+        let actual = render_markdown(
+            "[Slug link]",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
         let expected = "<p><a href=\"slug-link\" title=\"Slug link\">Slug link</a></p>\n";
         assert_eq!(actual, expected);
     }
 
     #[test]
     fn footnote_links() {
-        let actual = render_markdown("[Link]\n\n[Link]: target");
+        let actual = render_markdown(
+            "[Link]\n\n[Link]: target",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
         let expected = "<p><a href=\"target\">Link</a></p>\n";
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn footnotes() {
+        let actual = render_markdown(
+            "Text[^1]\n\n[^1]: Note",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p>Text<sup class=\"footnote-reference\"><a href=\"#1\">1</a></sup></p>\n\
+            <div class=\"footnote-definition\" id=\"1\"><sup class=\"footnote-definition-label\">1</sup>\n\
+            <p>Note</p>\n</div>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn strikethrough() {
+        let actual = render_markdown(
+            "a ~~b *c*~~ d `~~e~~` ~~f\n\n~~g~~~~h~~",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p>a <del>b <em>c</em></del> d <code>~~e~~</code> ~~f</p>\n\
+            <p><del>g</del><del>h</del></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn strikethrough_nesting() {
+        let actual = render_markdown(
+            "**~~a** b~~\n\n~~**a** b~~\n\n**~~a~~** ~~[b ~~c](d)~~\n\n~~a *b~~ c*",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p><strong>~~a</strong> b~~</p>\n\
+            <p><del><strong>a</strong> b</del></p>\n\
+            <p><strong><del>a</del></strong> <del><a href=\"d\">b ~~c</a></del></p>\n\
+            <p>~~a <em>b~~ c</em></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn task_lists() {
+        let src = "- [ ] Todo\n- [x] Done\n- [Link] item";
+        let actual = render_markdown(src, &HashSet::new(), &HashMap::new(), &allowed_schemes());
+        let expected = "<ul>\n\
+            <li class=\"task\"><input type=\"checkbox\" disabled> Todo</li>\n\
+            <li class=\"task\"><input type=\"checkbox\" disabled checked> Done</li>\n\
+            <li><a href=\"link\" title=\"Link\">Link</a> item</li>\n\
+            </ul>\n";
+        assert_eq!(actual, expected);
+        assert_eq!(link_targets(src), vec!["link"]);
+
+        // Also when `[x]` is a reference link, and only at the start of items
+        let src = "1. [X] Nested\n   - [ ] `code`\n2. Not [ ] a task\n3. [ ]\n\n[x]: https://example.com/";
+        let actual = render_markdown(src, &HashSet::new(), &HashMap::new(), &allowed_schemes());
+        let expected = "<ol>\n\
+            <li class=\"task\"><input type=\"checkbox\" disabled checked> Nested\n\
+            <ul>\n\
+            <li class=\"task\"><input type=\"checkbox\" disabled> <code>code</code></li>\n\
+            </ul>\n\
+            </li>\n\
+            <li>Not <a href=\"\" title=\" \"> </a> a task</li>\n\
+            <li><a href=\"\" title=\" \"> </a></li>\n\
+            </ol>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn task_lists_with_short_and_non_ascii_items() {
+        let render =
+            |src| render_markdown(src, &HashSet::new(), &HashMap::new(), &allowed_schemes());
+
+        assert_eq!(render("- a\n"), "<ul>\n<li>a</li>\n</ul>\n");
+        assert_eq!(render("x\n\n- a\n"), "<p>x</p>\n<ul>\n<li>a</li>\n</ul>\n");
+        assert_eq!(
+            render("1. [ ] x\n2. y\n"),
+            "<ol>\n<li class=\"task\"><input type=\"checkbox\" disabled> x</li>\n<li>y</li>\n</ol>\n"
+        );
+        assert_eq!(
+            render("- [ ]\n- x\n"),
+            "<ul>\n<li><a href=\"\" title=\" \"> </a></li>\n<li>x</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render("- I år var det bra\n- 5 € totalt\n"),
+            "<ul>\n<li>I år var det bra</li>\n<li>5 € totalt</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render_markdown_for_fts("- 5 € totalt\n- a"),
+            "  5 € totalt  a  "
+        );
+    }
+
+    #[test]
+    fn math() {
+        let actual = render_markdown(
+            "$a_1 * b_1$ costs $5 to $10, `$x$`\n\n$$\\{x\\}$$",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = format!(
+            "<p>{} costs $5 to $10, <code>$x$</code></p>\n<p>{}</p>\n",
+            to_mathml("a_1 * b_1", false),
+            to_mathml("\\{x\\}", true),
+        );
+        assert_eq!(actual, expected);
+
+        let actual = render_markdown_for_fts("Energy: $E = mc^2$");
+        assert_eq!(actual, " Energy: E = mc^2 ");
+    }
+
+    #[test]
+    fn math_with_placeholder_characters() {
+        let src = "$x$ \u{E000}0\u{E001} \u{E000}9\u{E001} \u{E000}a \u{E001}";
+        let actual = render_markdown(src, &HashSet::new(), &HashMap::new(), &allowed_schemes());
+        let expected = format!(
+            "<p>{} \u{FFFD}0\u{FFFD} \u{FFFD}9\u{FFFD} \u{FFFD}a \u{FFFD}</p>\n",
+            to_mathml("x", false)
+        );
+        assert_eq!(actual, expected);
+
+        let actual = render_markdown_for_fts(src);
+        assert_eq!(
+            actual,
+            " x \u{FFFD}0\u{FFFD} \u{FFFD}9\u{FFFD} \u{FFFD}a \u{FFFD} "
+        );
+    }
+
+    #[test]
+    fn plain_text() {
+        let src = "---\ntags: a\n---\n# Head\n\nSome *text*, [a link](other) and $x^2$.\n\n\
+            1. One\n2. Two\n    - [x] Nested\n\n```\ncode\n```\n\n| A | B |\n|---|---|\n| 1 | 2 |\n";
+        let expected = "Title\n=====\n\n\
+            Head\n\n\
+            Some text, a link <other> and x^2.\n\n\
+            1. One\n2. Two\n  - [x] Nested\n\n\
+            code\n\n\
+            A\tB\n1\t2\n";
+        assert_eq!(render_markdown_as_text("Title", src), expected);
+    }
+
+    #[test]
+    fn unsafe_links() {
+        let actual = render_markdown(
+            "[a](javascript:alert(1)), [b](JAVA\tSCRIPT:x), ![c](data:text/html,x), \
+                [d](https://example.com/ \"T\"), [e](mailto:a@example.com), [f](other#x)",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p>a, b, c, \
+            <a href=\"https://example.com/\" title=\"T\" class=\"external-link\" rel=\"nofollow noopener\">d</a>, \
+            <a href=\"mailto:a@example.com\">e</a>, <a href=\"other#x\">f</a></p>\n";
+        assert_eq!(actual, expected);
+
+        let allowed_schemes = vec!["gopher".to_owned()];
+        let actual = render_markdown(
+            "[a](gopher://example.com/), [b](https://example.com/), [c](other)",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes,
+        );
+        let expected =
+            "<p><a href=\"gopher://example.com/\">a</a>, b, <a href=\"other\">c</a></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn missing_link() {
+        let missing = ["missing".to_owned()].iter().cloned().collect();
+        let actual = render_markdown(
+            "[Missing], [Existing](existing)",
+            &missing,
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p><a href=\"missing\" class=\"missing-article\" \
+            title=\"Missing (article does not exist)\">Missing</a>, \
+            <a href=\"existing\">Existing</a></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn link_targets_are_local_slugs() {
+        let actual = link_targets(
+            "[Slug link] [Other](other#section) [Ext](https://example.com/) \
+             [Changes](_changes) [Front page](.) [Again](slug-link)",
+        );
+        assert_eq!(actual, vec!["other", "slug-link"]);
+    }
+
+    #[test]
+    fn front_matter_is_not_rendered() {
+        let actual = render_markdown(
+            "---\ntags: A, b c\n---\nText",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p>Text</p>\n<p class=\"tags\">Tags: \
+            <a href=\"_tags/a\">a</a>, <a href=\"_tags/b-c\">b-c</a></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn tags_from_front_matter() {
+        assert_eq!(
+            tags("---\ntitle: x\ntags: [Board games, \"a\"]\n---\n"),
+            vec!["a", "board-games"]
+        );
+        assert!(tags("tags: a").is_empty());
+        assert!(tags("---\ntags: a").is_empty());
+    }
+
+    #[test]
+    fn front_matter_is_rendered_as_infobox() {
+        let actual = render_markdown(
+            "+++\nstatus = \"draft\"\n+++\nText",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<table class=\"infobox\">\n\
+            <tr><th>status</th><td><a href=\"_properties?key=status&amp;value=draft\">draft</a></td></tr>\n\
+            </table>\n<p>Text</p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn properties_from_front_matter() {
+        let actual = properties(
+            "---\n# A comment\nOwner: Alice\nstatus: 'draft'\ntags: a\nreview: 2026-10-18 12:00\nstatus: final\n---\n",
+        );
+        let expected = vec![
+            ("owner".to_owned(), "Alice".to_owned()),
+            ("review".to_owned(), "2026-10-18 12:00".to_owned()),
+            ("status".to_owned(), "final".to_owned()),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn heading_ids_are_unique() {
+        let actual = render_markdown(
+            "# Intro\n\n## Intro\n\n## `Code` *here*\n\n## !",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<h1 id=\"intro\">Intro</h1>\n\
+            <h2 id=\"intro-2\">Intro</h2>\n\
+            <h2 id=\"code-here\"><code>Code</code> <em>here</em></h2>\n\
+            <h2 id=\"section\">!</h2>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn table_of_contents_for_long_articles() {
+        assert!(table_of_contents("# A\n\n# B", 3).is_empty());
+
+        let toc = table_of_contents("# A\n\n## B\n\n## B\n\n# C", 3);
+        assert_eq!(
+            vec![(1, "a"), (2, "b"), (2, "b-2"), (1, "c")],
+            toc.iter()
+                .map(|x| (x.level, x.id.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn sections() {
+        let src =
+            "---\nstatus: draft\n---\nIntro\n\n# One\n\nText\n\nTwo\n---\n\nMore\n\n# Three\n";
+        assert_eq!(section(src, 0), None);
+        assert_eq!(
+            section(src, 1),
+            Some("# One\n\nText\n\nTwo\n---\n\nMore\n\n")
+        );
+        assert_eq!(section(src, 2), Some("Two\n---\n\nMore\n\n"));
+        assert_eq!(section(src, 3), Some("# Three\n"));
+        assert_eq!(section(src, 4), None);
+
+        assert_eq!(
+            replace_section(src, 2, "Changed").unwrap(),
+            "---\nstatus: draft\n---\nIntro\n\n# One\n\nText\n\nChanged\n\n# Three\n"
+        );
+    }
+
+    #[test]
+    fn wiki_links() {
+        let actual = render_markdown(
+            "[[Target Article|display *text*]], [[Target Article#Section]], `[[Code]]`",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p><a href=\"target-article\" title=\"Target Article\">display <em>text</em></a>, \
+            <a href=\"target-article#section\" title=\"Target Article#Section\">Target Article#Section</a>, \
+            <code>[[Code]]</code></p>\n";
+        assert_eq!(actual, expected);
+
+        assert_eq!(link_targets("[[Target|label]]"), vec!["target"]);
+
+        let actual = render_markdown(
+            "[[*A|b*]], [[**A**|b]]",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected = "<p><a href=\"a\" title=\"*A\"><em>A|b</em></a>, \
+            <a href=\"a\" title=\"**A**\">b</a></p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn wiki_links_for_fts() {
+        let actual = render_markdown_for_fts("[[Target|label]]");
+        assert_eq!(actual, "  label (target)  ");
+    }
+
+    #[test]
+    fn code_blocks_are_highlighted() {
+        let actual = render_markdown(
+            "```sh\necho $HOME\n```\n\n```text\n$HOME\n```",
+            &HashSet::new(),
+            &HashMap::new(),
+            &allowed_schemes(),
+        );
+        let expected =
+            "<pre><code class=\"language-sh\">echo <span class=\"hl-variable\">$HOME</span>\n\
+            </code></pre>\n\
+            <pre><code class=\"language-text\">$HOME\n</code></pre>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn includes() {
+        let src = "{{include:On call}}\n\n{{include:missing}} \n\nText {{include:inline}}";
+        assert_eq!(
+            directives(src),
+            vec![
+                Directive::Include("missing".to_owned()),
+                Directive::Include("on-call".to_owned()),
+            ]
+        );
+
+        let mut expansions = HashMap::new();
+        expansions.insert(
+            Directive::Include("on-call".to_owned()),
+            Expansion::Rendered("<p>Call me</p>\n".to_owned()),
+        );
+        let actual = render_markdown(src, &HashSet::new(), &expansions, &allowed_schemes());
+        let expected = "<div class=\"included\">\n<p>Call me</p>\n</div>\n\
+            <p class=\"include-error\">Cannot include <a href=\"missing\">missing</a>: \
+            the article does not exist</p>\n\
+            <p>Text {{include:inline}}</p>\n";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn macros() {
+        assert_eq!(
+            parse_directive("{{recent-changes n=5}}"),
+            Some(Directive::RecentChanges { limit: 5 })
+        );
+        assert_eq!(
+            parse_directive("{{ recent-changes }}"),
+            Some(Directive::RecentChanges {
+                limit: DEFAULT_MACRO_LIMIT
+            })
+        );
+        assert_eq!(
+            parse_directive("{{article-list tag=\"On call\"}}"),
+            Some(Directive::ArticleList {
+                tag: Some("on-call".to_owned())
+            })
+        );
+        assert_eq!(
+            parse_directive("{{search q=\"foo bar\" n=3}}"),
+            Some(Directive::Search {
+                query: "foo bar".to_owned(),
+                limit: 3
+            })
+        );
+        assert_eq!(parse_directive("{{children}}"), Some(Directive::Children));
+
+        assert_eq!(parse_directive("{{recent-changes n=0}}"), None);
+        assert_eq!(parse_directive("{{search}}"), None);
+        assert_eq!(parse_directive("{{children n=5}}"), None);
+        assert_eq!(parse_directive("{{unknown}}"), None);
+
+        let mut expansions = HashMap::new();
+        expansions.insert(
+            Directive::RecentChanges { limit: 2 },
+            Expansion::Articles(vec![ListedArticle {
+                title: "A & B".to_owned(),
+                link: "a-b".to_owned(),
+                note: Some("today".to_owned()),
+            }]),
+        );
+        let actual = render_markdown(
+            "{{recent-changes n=2}}\n\n{{children}}",
+            &HashSet::new(),
+            &expansions,
+            &allowed_schemes(),
+        );
+        let expected = "<ul class=\"macro-list\">\n\
+            <li><a href=\"a-b\">A &amp; B</a> <span class=\"note\">today</span></li>\n\
+            </ul>\n\
+            <p class=\"macro-list\">No articles</p>\n";
+        assert_eq!(actual, expected);
+    }
 }
@@ -0,0 +1,143 @@
+use futures::{self, Future};
+
+use hyper::header::{
+    Accept, Charset, ContentDisposition, ContentType, DispositionParam, DispositionType,
+    QualityItem,
+};
+use hyper::mime;
+use hyper::server::*;
+
+use crate::build_config;
+use crate::mimes::*;
+use crate::models;
+use crate::rendering;
+use crate::state::State;
+use crate::theme::Theme;
+use crate::web::ResponseFuture;
+
+// Articles can be downloaded in these formats instead of as a wiki page,
+// chosen by `?format=` or the Accept header
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArticleFormat {
+    Markdown,
+    Text,
+    Html,
+}
+
+impl ArticleFormat {
+    pub fn from_name(name: &str) -> Option<ArticleFormat> {
+        match name {
+            "markdown" | "md" => Some(ArticleFormat::Markdown),
+            "text" | "txt" => Some(ArticleFormat::Text),
+            "html" => Some(ArticleFormat::Html),
+            _ => None,
+        }
+    }
+
+    // `text/html` is the wiki page, so the standalone document is only
+    // available by `?format=html`
+    pub fn from_accept(accept: &Accept) -> Option<ArticleFormat> {
+        match accept.first() {
+            Some(QualityItem { item: mime, .. }) if mime.type_() == mime::TEXT => {
+                match mime.subtype().as_str() {
+                    "markdown" | "x-markdown" => Some(ArticleFormat::Markdown),
+                    "plain" => Some(ArticleFormat::Text),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> ContentType {
+        ContentType(match self {
+            ArticleFormat::Markdown => TEXT_MARKDOWN.clone(),
+            ArticleFormat::Text => TEXT_PLAIN.clone(),
+            ArticleFormat::Html => TEXT_HTML.clone(),
+        })
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ArticleFormat::Markdown => "md",
+            ArticleFormat::Text => "txt",
+            ArticleFormat::Html => "html",
+        }
+    }
+
+    // The file is named after the slug, or `index` for the front page
+    pub fn content_disposition(self, slug: &str, revision: Option<i32>) -> ContentDisposition {
+        let slug = if slug.is_empty() { "index" } else { slug };
+        let name = match revision {
+            Some(revision) => format!("{}-{}", slug, revision),
+            None => slug.to_owned(),
+        };
+
+        ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(
+                Charset::Ext("UTF-8".to_owned()),
+                None,
+                format!("{}.{}", name, self.extension()).into_bytes(),
+            )],
+        }
+    }
+}
+
+#[derive(BartDisplay)]
+#[template = "templates/article_standalone.html"]
+struct Standalone<'a> {
+    title: &'a str,
+    theme: Theme,
+    rendered: String,
+}
+
+impl<'a> Standalone<'a> {
+    fn themes_css(&self) -> &'static str {
+        include_str!("../../assets/themes.css")
+    }
+    fn style_css(&self) -> &'static str {
+        include_str!("../../assets/style.css")
+    }
+
+    fn project_name(&self) -> &str {
+        build_config::PROJECT_NAME
+    }
+    fn version(&self) -> &str {
+        build_config::VERSION.as_str()
+    }
+}
+
+// Old revisions have the revision number in their file name
+pub fn download(
+    state: &State,
+    format: ArticleFormat,
+    data: models::ArticleRevision,
+) -> ResponseFuture {
+    let revision = Some(data.revision).filter(|_| !data.latest);
+    let response = Response::new()
+        .with_status(hyper::StatusCode::Ok)
+        .with_header(format.content_type())
+        .with_header(format.content_disposition(&data.slug, revision));
+
+    match format {
+        ArticleFormat::Markdown => Box::new(futures::finished(response.with_body(data.body))),
+        ArticleFormat::Text => Box::new(futures::finished(
+            response.with_body(rendering::render_markdown_as_text(&data.title, &data.body)),
+        )),
+        ArticleFormat::Html => Box::new(
+            state
+                .render_revision(data.article_id, data.revision, data.body.clone())
+                .map(move |rendered| {
+                    response.with_body(
+                        Standalone {
+                            title: &data.title,
+                            theme: data.theme,
+                            rendered,
+                        }
+                        .to_string(),
+                    )
+                }),
+        ),
+    }
+}
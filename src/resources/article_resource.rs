@@ -1,17 +1,19 @@
 use chrono::{DateTime, Local, TimeZone};
 use futures::{self, Future};
+use futures_cpupool::CpuFuture;
 
-use hyper::header::{ContentType, Location};
+use hyper::header::{Accept, ContentType, Location};
 use hyper::server::*;
 
 use crate::assets::ScriptJs;
 use crate::mimes::*;
-use crate::rendering::render_markdown;
-use crate::site::Layout;
+use crate::rendering::{self, Heading};
+use crate::site::{system_page, Layout};
 use crate::state::{RebaseConflict, State, UpdateResult};
 use crate::theme::{self, Theme};
-use crate::web::{Resource, ResponseFuture};
+use crate::web::{Error, Resource, ResponseFuture};
 
+use super::article_format::{self, ArticleFormat};
 use super::changes_resource::QueryParameters;
 
 struct SelectableTheme {
@@ -24,12 +26,16 @@ struct SelectableTheme {
 struct Template<'a> {
     revision: i32,
     last_updated: Option<&'a str>,
+    backlinks_link: Option<String>,
 
     edit: bool,
+    action: &'a str,
     cancel_url: Option<&'a str>,
     title: &'a str,
+    section: Option<usize>,
     raw: &'a str,
     rendered: String,
+    toc: Vec<Heading>,
     themes: &'a [SelectableTheme],
 }
 
@@ -39,12 +45,23 @@ impl<'a> Template<'a> {
     }
 }
 
+#[derive(BartDisplay)]
+#[template = "templates/rebase_too_long.html"]
+struct RebaseTooLong;
+
+const REBASE_TOO_LONG: &str = "This edit is based on a revision that is too old to be merged \
+    with the current version of the article. Reload the article and make the edit again.";
+
 #[derive(Deserialize)]
 struct UpdateArticle {
     base_revision: i32,
     title: String,
     body: String,
     theme: Option<Theme>,
+    summary: Option<String>,
+    #[serde(default)]
+    minor: bool,
+    section: Option<usize>,
 }
 
 pub struct ArticleResource {
@@ -52,15 +69,56 @@ pub struct ArticleResource {
     article_id: i32,
     revision: i32,
     edit: bool,
+    section: Option<usize>,
+    format: Option<ArticleFormat>,
 }
 
 impl ArticleResource {
-    pub fn new(state: State, article_id: i32, revision: i32, edit: bool) -> Self {
+    pub fn new(
+        state: State,
+        article_id: i32,
+        revision: i32,
+        edit: bool,
+        section: Option<usize>,
+        format: Option<ArticleFormat>,
+    ) -> Self {
         Self {
             state,
             article_id,
             revision,
             edit,
+            section,
+            format,
+        }
+    }
+
+    fn update(
+        &self,
+        update: UpdateArticle,
+        identity: Option<String>,
+    ) -> CpuFuture<UpdateResult, crate::state::Error> {
+        match update.section {
+            Some(section) => self.state.update_article_section(
+                self.article_id,
+                update.base_revision,
+                section,
+                update.title,
+                update.body,
+                identity,
+                update.theme,
+                update.summary,
+                update.minor,
+            ),
+            None => self.state.update_article(
+                self.article_id,
+                update.base_revision,
+                update.title,
+                update.body,
+                identity,
+                update.theme,
+                update.summary,
+                update.minor,
+            ),
         }
     }
 }
@@ -100,28 +158,113 @@ pub fn last_updated(article_id: i32, created: &DateTime<Local>, author: Option<&
     .to_string()
 }
 
+pub fn rebase_conflict_page(
+    state: &State,
+    base: Option<&'static str>,
+    action: String,
+    conflict: RebaseConflict,
+) -> impl Future<Item = String, Error = Error> {
+    let RebaseConflict {
+        base_article,
+        title,
+        body,
+        theme,
+    } = conflict;
+
+    let title = title.flatten();
+    let body = body.flatten();
+    let rendered = state.render_markdown(Some(base_article.article_id), body.clone());
+    let toc = state.table_of_contents(&body);
+
+    rendered.map(move |rendered| {
+        Layout {
+            base,
+            title: &title,
+            theme,
+            body: &Template {
+                revision: base_article.revision,
+                last_updated: Some(&last_updated(
+                    base_article.article_id,
+                    &Local.from_utc_datetime(&base_article.created),
+                    base_article.author.as_deref(),
+                )),
+                backlinks_link: Some(format!("_backlinks/{}", base_article.article_id)),
+                edit: true,
+                action: &action,
+                cancel_url: Some(base_article.link()),
+                title: &title,
+                section: None,
+                raw: &body,
+                rendered,
+                toc,
+                themes: &theme::THEMES
+                    .iter()
+                    .map(|&x| SelectableTheme {
+                        theme: x,
+                        selected: x == theme,
+                    })
+                    .collect::<Vec<_>>(),
+            },
+        }
+        .to_string()
+    })
+}
+
 impl Resource for ArticleResource {
     fn allow(&self) -> Vec<hyper::Method> {
         use hyper::Method::*;
         vec![Options, Head, Get, Put, Post]
     }
 
+    fn hacky_inject_accept_header(&mut self, accept: Accept) {
+        if self.format.is_none() && !self.edit {
+            self.format = ArticleFormat::from_accept(&accept);
+        }
+    }
+
     fn head(&self) -> ResponseFuture {
+        let content_type = match self.format {
+            Some(format) => format.content_type(),
+            None => ContentType(TEXT_HTML.clone()),
+        };
+
         Box::new(futures::finished(
             Response::new()
                 .with_status(hyper::StatusCode::Ok)
-                .with_header(ContentType(TEXT_HTML.clone())),
+                .with_header(content_type),
         ))
     }
 
     fn get(self: Box<Self>) -> ResponseFuture {
+        if let Some(format) = self.format {
+            let state = self.state.clone();
+            return Box::new(
+                self.state
+                    .get_article_revision(self.article_id, self.revision)
+                    .map(|x| x.expect("Data model guarantees that this exists"))
+                    .and_then(move |data| article_format::download(&state, format, data)),
+            );
+        }
+
+        let state = self.state.clone();
         let data = self
             .state
             .get_article_revision(self.article_id, self.revision)
-            .map(|x| x.expect("Data model guarantees that this exists"));
+            .map(|x| x.expect("Data model guarantees that this exists"))
+            .and_then(move |data| {
+                state
+                    .render_revision(data.article_id, data.revision, data.body.clone())
+                    .map(move |rendered| (data, rendered))
+            });
         let head = self.head();
 
-        Box::new(data.join(head).and_then(move |(data, head)| {
+        Box::new(data.join(head).and_then(move |((data, rendered), head)| {
+            // Editing a single section when it exists, or else the whole article
+            let section = self
+                .section
+                .filter(|_| self.edit)
+                .and_then(|n| rendering::section(&data.body, n).map(|raw| (n, raw)));
+
             Ok(head.with_body(
                 Layout {
                     base: None, // Hmm, should perhaps accept `base` as argument
@@ -134,11 +277,15 @@ impl Resource for ArticleResource {
                             &Local.from_utc_datetime(&data.created),
                             data.author.as_deref(),
                         )),
+                        backlinks_link: Some(format!("_backlinks/{}", data.article_id)),
                         edit: self.edit,
+                        action: "",
                         cancel_url: Some(data.link()),
                         title: &data.title,
-                        raw: &data.body,
-                        rendered: render_markdown(&data.body),
+                        section: section.map(|(n, _)| n),
+                        raw: section.map_or(&data.body, |(_, raw)| raw),
+                        rendered,
+                        toc: self.state.table_of_contents(&data.body),
                         themes: &theme::THEMES
                             .iter()
                             .map(|&x| SelectableTheme {
@@ -163,6 +310,7 @@ impl Resource for ArticleResource {
         struct Template<'a> {
             title: &'a str,
             rendered: String,
+            toc: Vec<Heading>,
         }
 
         #[derive(Serialize)]
@@ -177,77 +325,95 @@ impl Resource for ArticleResource {
             last_updated: &'a str,
         }
 
+        let state = self.state.clone();
+
         Box::new(
             body.concat2()
                 .map_err(Into::into)
                 .and_then(|body| serde_urlencoded::from_bytes(&body).map_err(Into::into))
-                .and_then(move |update: UpdateArticle| {
-                    self.state.update_article(
-                        self.article_id,
-                        update.base_revision,
-                        update.title,
-                        update.body,
-                        identity,
-                        update.theme,
-                    )
-                })
-                .and_then(|updated| match updated {
-                    UpdateResult::Success(updated) => Ok(Response::new()
-                        .with_status(hyper::StatusCode::Ok)
-                        .with_header(ContentType(APPLICATION_JSON.clone()))
-                        .with_body(
-                            serde_json::to_string(&PutResponse {
-                                conflict: false,
-                                slug: &updated.slug,
-                                revision: updated.revision,
-                                title: &updated.title,
-                                body: &updated.body,
-                                theme: updated.theme,
-                                rendered: &Template {
-                                    title: &updated.title,
-                                    rendered: render_markdown(&updated.body),
-                                }
-                                .to_string(),
-                                last_updated: &last_updated(
-                                    updated.article_id,
-                                    &Local.from_utc_datetime(&updated.created),
-                                    updated.author.as_deref(),
-                                ),
-                            })
-                            .expect("Should never fail"),
+                .and_then(move |update: UpdateArticle| self.update(update, identity))
+                .and_then(move |updated| -> ResponseFuture {
+                    match updated {
+                        UpdateResult::Success(updated) => {
+                            let rendered = state.render_revision(
+                                updated.article_id,
+                                updated.revision,
+                                updated.body.clone(),
+                            );
+                            let toc = state.table_of_contents(&updated.body);
+                            Box::new(rendered.map(move |rendered| {
+                                Response::new()
+                                    .with_status(hyper::StatusCode::Ok)
+                                    .with_header(ContentType(APPLICATION_JSON.clone()))
+                                    .with_body(
+                                        serde_json::to_string(&PutResponse {
+                                            conflict: false,
+                                            slug: &updated.slug,
+                                            revision: updated.revision,
+                                            title: &updated.title,
+                                            body: &updated.body,
+                                            theme: updated.theme,
+                                            rendered: &Template {
+                                                title: &updated.title,
+                                                rendered,
+                                                toc,
+                                            }
+                                            .to_string(),
+                                            last_updated: &last_updated(
+                                                updated.article_id,
+                                                &Local.from_utc_datetime(&updated.created),
+                                                updated.author.as_deref(),
+                                            ),
+                                        })
+                                        .expect("Should never fail"),
+                                    )
+                            }))
+                        }
+                        UpdateResult::RebaseTooLong => Box::new(futures::finished(
+                            Response::new()
+                                .with_status(hyper::StatusCode::Conflict)
+                                .with_header(ContentType(TEXT_PLAIN.clone()))
+                                .with_body(REBASE_TOO_LONG),
                         )),
-                    UpdateResult::RebaseConflict(RebaseConflict {
-                        base_article,
-                        title,
-                        body,
-                        theme,
-                    }) => {
-                        let title = title.flatten();
-                        let body = body.flatten();
-                        Ok(Response::new()
-                            .with_status(hyper::StatusCode::Ok)
-                            .with_header(ContentType(APPLICATION_JSON.clone()))
-                            .with_body(
-                                serde_json::to_string(&PutResponse {
-                                    conflict: true,
-                                    slug: &base_article.slug,
-                                    revision: base_article.revision,
-                                    title: &title,
-                                    body: &body,
-                                    theme,
-                                    rendered: &Template {
-                                        title: &title,
-                                        rendered: render_markdown(&body),
-                                    }
-                                    .to_string(),
-                                    last_updated: &last_updated(
-                                        base_article.article_id,
-                                        &Local.from_utc_datetime(&base_article.created),
-                                        base_article.author.as_deref(),
-                                    ),
-                                })
-                                .expect("Should never fail"),
-                            ))
+                        UpdateResult::RebaseConflict(RebaseConflict {
+                            base_article,
+                            title,
+                            body,
+                            theme,
+                        }) => {
+                            let title = title.flatten();
+                            let body = body.flatten();
+                            let rendered =
+                                state.render_markdown(Some(base_article.article_id), body.clone());
+                            let toc = state.table_of_contents(&body);
+                            Box::new(rendered.map(move |rendered| {
+                                Response::new()
+                                    .with_status(hyper::StatusCode::Ok)
+                                    .with_header(ContentType(APPLICATION_JSON.clone()))
+                                    .with_body(
+                                        serde_json::to_string(&PutResponse {
+                                            conflict: true,
+                                            slug: &base_article.slug,
+                                            revision: base_article.revision,
+                                            title: &title,
+                                            body: &body,
+                                            theme,
+                                            rendered: &Template {
+                                                title: &title,
+                                                rendered,
+                                                toc,
+                                            }
+                                            .to_string(),
+                                            last_updated: &last_updated(
+                                                base_article.article_id,
+                                                &Local.from_utc_datetime(&base_article.created),
+                                                base_article.author.as_deref(),
+                                            ),
+                                        })
+                                        .expect("Should never fail"),
+                                    )
+                            }))
+                        }
                     }
                 }),
         )
@@ -258,65 +424,40 @@ impl Resource for ArticleResource {
 
         use futures::Stream;
 
+        let state = self.state.clone();
+
         Box::new(
             body.concat2()
                 .map_err(Into::into)
                 .and_then(|body| serde_urlencoded::from_bytes(&body).map_err(Into::into))
-                .and_then(move |update: UpdateArticle| {
-                    self.state.update_article(
-                        self.article_id,
-                        update.base_revision,
-                        update.title,
-                        update.body,
-                        identity,
-                        update.theme,
-                    )
-                })
-                .and_then(|updated| match updated {
-                    UpdateResult::Success(updated) => Ok(Response::new()
-                        .with_status(hyper::StatusCode::SeeOther)
-                        .with_header(ContentType(TEXT_PLAIN.clone()))
-                        .with_header(Location::new(updated.link().to_owned()))
-                        .with_body("See other")),
-                    UpdateResult::RebaseConflict(RebaseConflict {
-                        base_article,
-                        title,
-                        body,
-                        theme,
-                    }) => {
-                        let title = title.flatten();
-                        let body = body.flatten();
-                        Ok(Response::new()
-                            .with_status(hyper::StatusCode::Ok)
-                            .with_header(ContentType(TEXT_HTML.clone()))
-                            .with_body(
-                                Layout {
-                                    base: None,
-                                    title: &title,
-                                    theme,
-                                    body: &Template {
-                                        revision: base_article.revision,
-                                        last_updated: Some(&last_updated(
-                                            base_article.article_id,
-                                            &Local.from_utc_datetime(&base_article.created),
-                                            base_article.author.as_deref(),
-                                        )),
-                                        edit: true,
-                                        cancel_url: Some(base_article.link()),
-                                        title: &title,
-                                        raw: &body,
-                                        rendered: render_markdown(&body),
-                                        themes: &theme::THEMES
-                                            .iter()
-                                            .map(|&x| SelectableTheme {
-                                                theme: x,
-                                                selected: x == theme,
-                                            })
-                                            .collect::<Vec<_>>(),
-                                    },
-                                }
-                                .to_string(),
-                            ))
+                .and_then(move |update: UpdateArticle| self.update(update, identity))
+                .and_then(move |updated| -> ResponseFuture {
+                    match updated {
+                        UpdateResult::Success(updated) => Box::new(futures::finished(
+                            Response::new()
+                                .with_status(hyper::StatusCode::SeeOther)
+                                .with_header(ContentType(TEXT_PLAIN.clone()))
+                                .with_header(Location::new(updated.link().to_owned()))
+                                .with_body("See other"),
+                        )),
+                        UpdateResult::RebaseConflict(conflict) => Box::new(
+                            rebase_conflict_page(&state, None, String::new(), conflict).map(
+                                |page| {
+                                    Response::new()
+                                        .with_status(hyper::StatusCode::Ok)
+                                        .with_header(ContentType(TEXT_HTML.clone()))
+                                        .with_body(page)
+                                },
+                            ),
+                        ),
+                        UpdateResult::RebaseTooLong => Box::new(futures::finished(
+                            Response::new()
+                                .with_status(hyper::StatusCode::Conflict)
+                                .with_header(ContentType(TEXT_HTML.clone()))
+                                .with_body(
+                                    system_page(None, "Conflict", RebaseTooLong).to_string(),
+                                ),
+                        )),
                     }
                 }),
         )
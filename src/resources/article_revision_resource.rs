@@ -1,26 +1,33 @@
 use chrono::{DateTime, Local, TimeZone};
 use futures::{self, Future};
 
-use hyper::header::ContentType;
+use hyper::header::{Accept, ContentType, Location};
 use hyper::server::*;
 
 use crate::mimes::*;
 use crate::models;
-use crate::rendering::render_markdown;
 use crate::site::system_page;
+use crate::state::State;
 use crate::web::{Resource, ResponseFuture};
 
+use super::article_format::{self, ArticleFormat};
 use super::changes_resource::QueryParameters;
 use super::diff_resource;
 use super::pagination::Pagination;
 
 pub struct ArticleRevisionResource {
+    state: State,
     data: models::ArticleRevision,
+    format: Option<ArticleFormat>,
 }
 
 impl ArticleRevisionResource {
-    pub fn new(data: models::ArticleRevision) -> Self {
-        Self { data }
+    pub fn new(state: State, data: models::ArticleRevision, format: Option<ArticleFormat>) -> Self {
+        Self {
+            state,
+            data,
+            format,
+        }
     }
 }
 
@@ -72,31 +79,56 @@ pub fn timestamp_and_author(
 impl Resource for ArticleRevisionResource {
     fn allow(&self) -> Vec<hyper::Method> {
         use hyper::Method::*;
-        vec![Options, Head, Get, Put]
+        vec![Options, Head, Get, Post]
+    }
+
+    fn hacky_inject_accept_header(&mut self, accept: Accept) {
+        if self.format.is_none() {
+            self.format = ArticleFormat::from_accept(&accept);
+        }
     }
 
     fn head(&self) -> ResponseFuture {
+        let content_type = match self.format {
+            Some(format) => format.content_type(),
+            None => ContentType(TEXT_HTML.clone()),
+        };
+
         Box::new(futures::finished(
             Response::new()
                 .with_status(hyper::StatusCode::Ok)
-                .with_header(ContentType(TEXT_HTML.clone())),
+                .with_header(content_type),
         ))
     }
 
     fn get(self: Box<Self>) -> ResponseFuture {
+        if let Some(format) = self.format {
+            return article_format::download(&self.state, format, self.data);
+        }
+
         #[derive(BartDisplay)]
         #[template = "templates/article_revision.html"]
         struct Template<'a> {
             link_current: &'a str,
             timestamp_and_author: &'a str,
+            summary: Option<&'a str>,
+            minor: bool,
+            deleted: bool,
+            restored_link: Option<String>,
+            revertable: bool,
             diff_link: Option<String>,
             rendered: String,
         }
 
         let head = self.head();
+        let rendered = self.state.render_revision(
+            self.data.article_id,
+            self.data.revision,
+            self.data.body.clone(),
+        );
         let data = self.data;
 
-        Box::new(head.and_then(move |head| {
+        Box::new(head.join(rendered).and_then(move |(head, rendered)| {
             Ok(head.with_body(
                 system_page(
                     Some("../../"), // Hmm, should perhaps accept `base` as argument
@@ -109,6 +141,13 @@ impl Resource for ArticleRevisionResource {
                             &Local.from_utc_datetime(&data.created),
                             data.author.as_deref(),
                         ),
+                        summary: data.summary.as_deref(),
+                        minor: data.minor,
+                        deleted: data.deleted,
+                        restored_link: data
+                            .restored_revision
+                            .map(|x| format!("_revisions/{}/{}", data.article_id, x)),
+                        revertable: !data.latest && !data.deleted,
                         diff_link: if data.revision > 1 {
                             Some(format!(
                                 "_diff/{}?{}",
@@ -121,11 +160,34 @@ impl Resource for ArticleRevisionResource {
                         } else {
                             None
                         },
-                        rendered: render_markdown(&data.body),
+                        rendered,
                     },
                 )
                 .to_string(),
             ))
         }))
     }
+    fn post(self: Box<Self>, body: hyper::Body, identity: Option<String>) -> ResponseFuture {
+        // TODO Check incoming Content-Type
+
+        use futures::Stream;
+
+        Box::new(
+            body.concat2()
+                .map_err(Into::into)
+                .and_then(move |_| {
+                    self.state
+                        .revert_article(self.data.article_id, self.data.revision, identity)
+                })
+                .and_then(|reverted| {
+                    futures::finished(
+                        Response::new()
+                            .with_status(hyper::StatusCode::SeeOther)
+                            .with_header(ContentType(TEXT_PLAIN.clone()))
+                            .with_header(Location::new(format!("../../{}", reverted.link())))
+                            .with_body("See other"),
+                    )
+                }),
+        )
+    }
 }
@@ -0,0 +1,62 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::models::ArticleRevisionStub;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+pub struct BacklinksResource {
+    state: State,
+    article_id: i32,
+}
+
+impl BacklinksResource {
+    pub fn new(state: State, article_id: i32) -> Self {
+        Self { state, article_id }
+    }
+}
+
+impl Resource for BacklinksResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        #[derive(BartDisplay)]
+        #[template = "templates/backlinks.html"]
+        struct Template<'a> {
+            article_id: i32,
+            articles: &'a [ArticleRevisionStub],
+        }
+
+        let data = self.state.get_backlinks(self.article_id);
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(articles, head)| {
+            Ok(head.with_body(
+                system_page(
+                    Some("../"), // Hmm, should perhaps accept `base` as argument
+                    "What links here",
+                    Template {
+                        article_id: self.article_id,
+                        articles: &articles,
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
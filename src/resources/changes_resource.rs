@@ -3,9 +3,10 @@ use futures::{self, Future};
 
 use hyper::header::ContentType;
 use hyper::server::*;
+use slug::slugify;
 
 use crate::mimes::*;
-use crate::schema::article_revisions;
+use crate::schema::{article_revisions, tags};
 use crate::site::system_page;
 use crate::state::State;
 use crate::web::{Resource, ResponseFuture};
@@ -31,6 +32,9 @@ pub struct QueryParameters {
 
     article_id: Option<i32>,
     author: Option<String>,
+    summary: Option<String>,
+    hide_minor: Option<bool>,
+    tag: Option<String>,
 
     limit: Option<i32>,
 }
@@ -60,6 +64,21 @@ impl QueryParameters {
         Self { author, ..self }
     }
 
+    pub fn summary(self, summary: Option<String>) -> Self {
+        Self { summary, ..self }
+    }
+
+    pub fn tag(self, tag: Option<String>) -> Self {
+        Self { tag, ..self }
+    }
+
+    pub fn hide_minor(self, hide_minor: bool) -> Self {
+        Self {
+            hide_minor: if hide_minor { Some(true) } else { None },
+            ..self
+        }
+    }
+
     pub fn limit(self, limit: i32) -> Self {
         Self {
             limit: if limit != DEFAULT_LIMIT {
@@ -85,6 +104,9 @@ fn apply_query_config(
     mut query: article_revisions::BoxedQuery<diesel::sqlite::Sqlite>,
     article_id: Option<i32>,
     author: Option<String>,
+    summary: Option<String>,
+    hide_minor: bool,
+    tag: Option<String>,
     limit: i32,
 ) -> article_revisions::BoxedQuery<diesel::sqlite::Sqlite> {
     use diesel::prelude::*;
@@ -97,6 +119,33 @@ fn apply_query_config(
         query = query.filter(article_revisions::author.eq(author));
     }
 
+    if let Some(summary) = summary {
+        // Search for the summary text literally, also when it contains `%` or `_`
+        let summary = summary
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        query = query.filter(
+            article_revisions::summary
+                .like(format!("%{}%", summary))
+                .escape('\\'),
+        );
+    }
+
+    if hide_minor {
+        query = query.filter(article_revisions::minor.eq(false));
+    }
+
+    if let Some(tag) = tag {
+        query = query.filter(
+            article_revisions::article_id.eq_any(
+                tags::table
+                    .filter(tags::tag.eq(tag))
+                    .select(tags::article_id),
+            ),
+        );
+    }
+
     query.limit(limit as i64 + 1)
 }
 
@@ -129,76 +178,115 @@ impl ChangesLookup {
                     _ => Err("`limit` argument must be in range [1, 100]"),
                 }?;
 
-                Ok((pagination, params.article_id, params.author, limit))
-            })())
-            .and_then(move |(pagination, article_id, author, limit)| {
-                match pagination {
-                    Pagination::After(x) => {
-                        let author2 = author.clone();
+                let summary = params.summary.filter(|x| !x.is_empty());
 
-                        Box::new(
-                            state
-                                .query_article_revision_stubs(move |query| {
-                                    use diesel::prelude::*;
+                let hide_minor = params.hide_minor.unwrap_or(false);
 
-                                    apply_query_config(query, article_id, author2, limit)
+                // Tags are stored as slugs
+                let tag = params.tag.map(slugify).filter(|x| !x.is_empty());
+
+                Ok((
+                    pagination,
+                    params.article_id,
+                    params.author,
+                    summary,
+                    hide_minor,
+                    tag,
+                    limit,
+                ))
+            })())
+            .and_then(
+                move |(pagination, article_id, author, summary, hide_minor, tag, limit)| {
+                    match pagination {
+                        Pagination::After(x) => {
+                            let author2 = author.clone();
+                            let summary2 = summary.clone();
+                            let tag2 = tag.clone();
+
+                            Box::new(
+                                state
+                                    .query_article_revision_stubs(move |query| {
+                                        use diesel::prelude::*;
+
+                                        apply_query_config(
+                                            query, article_id, author2, summary2, hide_minor, tag2,
+                                            limit,
+                                        )
                                         .filter(article_revisions::sequence_number.gt(x))
                                         .order(article_revisions::sequence_number.asc())
-                                })
-                                .and_then(move |mut data| {
-                                    let extra_element = if data.len() > limit as usize {
-                                        data.pop()
-                                    } else {
-                                        None
-                                    };
-
-                                    let args = QueryParameters {
-                                        after: None,
-                                        before: None,
-                                        article_id,
-                                        author,
-                                        limit: None,
-                                    }
-                                    .limit(limit);
-
-                                    Ok(Some(match extra_element {
-                                        Some(x) => Box::new(TemporaryRedirectResource::new(
-                                            args.pagination(Pagination::Before(x.sequence_number))
+                                    })
+                                    .and_then(move |mut data| {
+                                        let extra_element = if data.len() > limit as usize {
+                                            data.pop()
+                                        } else {
+                                            None
+                                        };
+
+                                        let args = QueryParameters {
+                                            after: None,
+                                            before: None,
+                                            article_id,
+                                            author,
+                                            summary,
+                                            hide_minor: None,
+                                            tag,
+                                            limit: None,
+                                        }
+                                        .hide_minor(hide_minor)
+                                        .limit(limit);
+
+                                        Ok(Some(match extra_element {
+                                            Some(x) => Box::new(TemporaryRedirectResource::new(
+                                                args.pagination(Pagination::Before(
+                                                    x.sequence_number,
+                                                ))
                                                 .into_link(),
-                                        ))
-                                            as BoxResource,
-                                        None => Box::new(TemporaryRedirectResource::new(
-                                            args.into_link(),
-                                        ))
-                                            as BoxResource,
-                                    }))
-                                }),
-                        )
-                            as Box<
-                                dyn Future<Item = Option<BoxResource>, Error = crate::web::Error>,
-                            >
-                    }
-                    Pagination::Before(x) => {
-                        Box::new(finished(Some(Box::new(ChangesResource::new(
-                            state,
-                            show_authors,
-                            Some(x),
-                            article_id,
-                            author,
-                            limit,
-                        )) as BoxResource)))
+                                            ))
+                                                as BoxResource,
+                                            None => Box::new(TemporaryRedirectResource::new(
+                                                args.into_link(),
+                                            ))
+                                                as BoxResource,
+                                        }))
+                                    }),
+                            )
+                                as Box<
+                                    dyn Future<
+                                        Item = Option<BoxResource>,
+                                        Error = crate::web::Error,
+                                    >,
+                                >
+                        }
+                        Pagination::Before(x) => {
+                            Box::new(finished(Some(Box::new(ChangesResource::new(
+                                state,
+                                show_authors,
+                                Some(x),
+                                article_id,
+                                author,
+                                summary,
+                                hide_minor,
+                                tag,
+                                limit,
+                            ))
+                                as BoxResource)))
+                        }
+                        Pagination::None => Box::new(finished(Some(
+                            Box::new(ChangesResource::new(
+                                state,
+                                show_authors,
+                                None,
+                                article_id,
+                                author,
+                                summary,
+                                hide_minor,
+                                tag,
+                                limit,
+                            )) as BoxResource,
+                        ))),
                     }
-                    Pagination::None => Box::new(finished(Some(Box::new(ChangesResource::new(
-                        state,
-                        show_authors,
-                        None,
-                        article_id,
-                        author,
-                        limit,
-                    ))
-                        as BoxResource))),
-                }
-            }),
+                },
+            ),
         )
     }
 }
@@ -209,16 +297,23 @@ pub struct ChangesResource {
     before: Option<i32>,
     article_id: Option<i32>,
     author: Option<String>,
+    summary: Option<String>,
+    hide_minor: bool,
+    tag: Option<String>,
     limit: i32,
 }
 
 impl ChangesResource {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         state: State,
         show_authors: bool,
         before: Option<i32>,
         article_id: Option<i32>,
         author: Option<String>,
+        summary: Option<String>,
+        hide_minor: bool,
+        tag: Option<String>,
         limit: i32,
     ) -> Self {
         Self {
@@ -227,6 +322,9 @@ impl ChangesResource {
             before,
             article_id,
             author,
+            summary,
+            hide_minor,
+            tag,
             limit,
         }
     }
@@ -237,8 +335,11 @@ impl ChangesResource {
             before: self.before,
             article_id: self.article_id,
             author: self.author.clone(),
+            summary: self.summary.clone(),
+            tag: self.tag.clone(),
             ..QueryParameters::default()
         }
+        .hide_minor(self.hide_minor)
         .limit(self.limit)
     }
 }
@@ -273,6 +374,10 @@ impl Resource for ChangesResource {
             title: String,
 
             _latest: bool,
+            deleted: bool,
+            restored_revision: Option<i32>,
+            summary: Option<String>,
+            minor: bool,
 
             diff_link: Option<String>,
         }
@@ -327,19 +432,67 @@ impl Resource for ChangesResource {
                     .as_ref()
                     .map(|_| self.resource.query_args().author(None).into_link())
             }
+
+            fn summary(&self) -> Option<String> {
+                self.resource.summary.clone()
+            }
+
+            fn article_id(&self) -> Option<i32> {
+                self.resource.article_id
+            }
+
+            fn tag(&self) -> Option<String> {
+                self.resource.tag.clone()
+            }
+
+            fn all_tags_link(&self) -> Option<String> {
+                self.resource
+                    .tag
+                    .as_ref()
+                    .map(|_| self.resource.query_args().tag(None).into_link())
+            }
+
+            fn hide_minor(&self) -> bool {
+                self.resource.hide_minor
+            }
+
+            fn hide_minor_link(&self) -> Option<String> {
+                match self.resource.hide_minor {
+                    false => Some(self.resource.query_args().hide_minor(true).into_link()),
+                    true => None,
+                }
+            }
+
+            fn show_minor_link(&self) -> Option<String> {
+                match self.resource.hide_minor {
+                    true => Some(self.resource.query_args().hide_minor(false).into_link()),
+                    false => None,
+                }
+            }
+
+            fn all_summaries_link(&self) -> Option<String> {
+                self.resource
+                    .summary
+                    .as_ref()
+                    .map(|_| self.resource.query_args().summary(None).into_link())
+            }
         }
 
-        let (before, article_id, author, limit) = (
+        let (before, article_id, author, summary, hide_minor, tag, limit) = (
             self.before,
             self.article_id,
             self.author.clone(),
+            self.summary.clone(),
+            self.hide_minor,
+            self.tag.clone(),
             self.limit,
         );
         let data = self.state.query_article_revision_stubs(move |query| {
             use diesel::prelude::*;
 
-            let query = apply_query_config(query, article_id, author, limit)
-                .order(article_revisions::sequence_number.desc());
+            let query =
+                apply_query_config(query, article_id, author, summary, hide_minor, tag, limit)
+                    .order(article_revisions::sequence_number.desc());
 
             match before {
                 Some(x) => query.filter(article_revisions::sequence_number.lt(x)),
@@ -403,6 +556,10 @@ impl Resource for ChangesResource {
                     _slug: x.slug,
                     title: x.title,
                     _latest: x.latest,
+                    deleted: x.deleted,
+                    restored_revision: x.restored_revision,
+                    summary: x.summary,
+                    minor: x.minor,
                     diff_link: if x.revision > 1 {
                         Some(format!(
                             "_diff/{}?{}",
@@ -0,0 +1,99 @@
+use futures::{self, Future};
+
+use hyper::header::{ContentType, Location};
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+#[derive(Deserialize)]
+struct DeleteArticle {
+    base_revision: i32,
+}
+
+pub struct DeleteArticleResource {
+    state: State,
+    article_id: i32,
+    revision: i32,
+}
+
+impl DeleteArticleResource {
+    pub fn new(state: State, article_id: i32, revision: i32) -> Self {
+        Self {
+            state,
+            article_id,
+            revision,
+        }
+    }
+}
+
+impl Resource for DeleteArticleResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get, Post]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        #[derive(BartDisplay)]
+        #[template = "templates/delete_article.html"]
+        struct Template<'a> {
+            link: &'a str,
+            revision: i32,
+        }
+
+        let data = self
+            .state
+            .get_article_revision(self.article_id, self.revision)
+            .map(|x| x.expect("Data model guarantees that this exists"));
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(data, head)| {
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    &data.title,
+                    &Template {
+                        link: data.link(),
+                        revision: data.revision,
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+
+    fn post(self: Box<Self>, body: hyper::Body, identity: Option<String>) -> ResponseFuture {
+        // TODO Check incoming Content-Type
+
+        use futures::Stream;
+
+        Box::new(
+            body.concat2()
+                .map_err(Into::into)
+                .and_then(|body| serde_urlencoded::from_bytes(&body).map_err(Into::into))
+                .and_then(move |arg: DeleteArticle| {
+                    self.state
+                        .delete_article(self.article_id, arg.base_revision, identity)
+                })
+                .and_then(|deleted| {
+                    futures::finished(
+                        Response::new()
+                            .with_status(hyper::StatusCode::SeeOther)
+                            .with_header(ContentType(TEXT_PLAIN.clone()))
+                            .with_header(Location::new(deleted.link().to_owned()))
+                            .with_body("See other"),
+                    )
+                }),
+        )
+    }
+}
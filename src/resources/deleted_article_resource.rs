@@ -0,0 +1,107 @@
+use chrono::{Local, TimeZone};
+use futures::{self, Future};
+
+use hyper::header::{ContentType, Location};
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+use super::article_revision_resource::timestamp_and_author;
+
+#[derive(Deserialize)]
+struct UndeleteArticle {
+    base_revision: i32,
+}
+
+pub struct DeletedArticleResource {
+    state: State,
+    article_id: i32,
+    revision: i32,
+}
+
+impl DeletedArticleResource {
+    pub fn new(state: State, article_id: i32, revision: i32) -> Self {
+        Self {
+            state,
+            article_id,
+            revision,
+        }
+    }
+}
+
+impl Resource for DeletedArticleResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get, Post]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Gone)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        #[derive(BartDisplay)]
+        #[template = "templates/deleted_article.html"]
+        struct Template<'a> {
+            timestamp_and_author: &'a str,
+            revision: i32,
+        }
+
+        let data = self
+            .state
+            .get_article_revision(self.article_id, self.revision)
+            .map(|x| x.expect("Data model guarantees that this exists"));
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(data, head)| {
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    &data.title,
+                    &Template {
+                        timestamp_and_author: &timestamp_and_author(
+                            data.sequence_number,
+                            data.article_id,
+                            &Local.from_utc_datetime(&data.created),
+                            data.author.as_deref(),
+                        ),
+                        revision: data.revision,
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+
+    fn post(self: Box<Self>, body: hyper::Body, identity: Option<String>) -> ResponseFuture {
+        // TODO Check incoming Content-Type
+
+        use futures::Stream;
+
+        Box::new(
+            body.concat2()
+                .map_err(Into::into)
+                .and_then(|body| serde_urlencoded::from_bytes(&body).map_err(Into::into))
+                .and_then(move |arg: UndeleteArticle| {
+                    self.state
+                        .undelete_article(self.article_id, arg.base_revision, identity)
+                })
+                .and_then(|undeleted| {
+                    futures::finished(
+                        Response::new()
+                            .with_status(hyper::StatusCode::SeeOther)
+                            .with_header(ContentType(TEXT_PLAIN.clone()))
+                            .with_header(Location::new(undeleted.link().to_owned()))
+                            .with_body("See other"),
+                    )
+                }),
+        )
+    }
+}
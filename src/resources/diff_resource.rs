@@ -3,16 +3,17 @@ use std::fmt;
 use futures::future::done;
 use futures::{self, Future};
 
-use hyper::header::ContentType;
+use hyper::header::{ContentType, Location};
 use hyper::server::*;
 
 use crate::mimes::*;
 use crate::models::ArticleRevision;
 use crate::site::Layout;
-use crate::state::State;
+use crate::state::{State, UpdateResult};
 use crate::theme;
 use crate::web::{Resource, ResponseFuture};
 
+use super::article_resource::rebase_conflict_page;
 use super::changes_resource;
 use super::pagination::Pagination;
 
@@ -59,12 +60,12 @@ impl DiffLookup {
                     let from = state.get_article_revision(article_id as i32, params.from as i32);
                     let to = state.get_article_revision(article_id as i32, params.to as i32);
 
-                    from.join(to)
+                    from.join(to).map(move |(from, to)| (state, from, to))
                 })
-                .and_then(move |(from, to)| match (from, to) {
-                    (Some(from), Some(to)) => {
-                        Ok(Some(Box::new(DiffResource::new(from, to)) as BoxResource))
-                    }
+                .and_then(|(state, from, to)| match (from, to) {
+                    (Some(from), Some(to)) => Ok(Some(
+                        Box::new(DiffResource::new(state, from, to)) as BoxResource,
+                    )),
                     _ => Ok(None),
                 }),
         )
@@ -72,21 +73,22 @@ impl DiffLookup {
 }
 
 pub struct DiffResource {
+    state: State,
     from: ArticleRevision,
     to: ArticleRevision,
 }
 
 impl DiffResource {
-    pub fn new(from: ArticleRevision, to: ArticleRevision) -> Self {
+    pub fn new(state: State, from: ArticleRevision, to: ArticleRevision) -> Self {
         assert_eq!(from.article_id, to.article_id);
-        Self { from, to }
+        Self { state, from, to }
     }
 }
 
 impl Resource for DiffResource {
     fn allow(&self) -> Vec<hyper::Method> {
         use hyper::Method::*;
-        vec![Options, Head, Get]
+        vec![Options, Head, Get, Post]
     }
 
     fn head(&self) -> ResponseFuture {
@@ -102,8 +104,10 @@ impl Resource for DiffResource {
         #[template = "templates/diff.html"]
         struct Template<'a> {
             consecutive: bool,
+            undoable: bool,
             article_id: u32,
             author: Option<&'a str>,
+            summary: Option<&'a str>,
             author_link: &'a str,
             article_history_link: &'a str,
             from_link: &'a str,
@@ -129,6 +133,11 @@ impl Resource for DiffResource {
                 false => None,
             };
 
+            let summary = match consecutive {
+                true => self.to.summary.as_deref(),
+                false => None,
+            };
+
             let author_link = &format!(
                 "_changes{}",
                 changes_resource::QueryParameters::default()
@@ -188,8 +197,10 @@ impl Resource for DiffResource {
                     theme: theme::theme_from_str_hash("Difference"),
                     body: &Template {
                         consecutive,
+                        undoable: consecutive && !self.from.deleted && !self.to.deleted,
                         article_id: self.from.article_id as u32,
                         author,
+                        summary,
                         author_link,
                         article_history_link,
                         from_link: &format!(
@@ -205,4 +216,55 @@ impl Resource for DiffResource {
             ))
         }))
     }
+
+    fn post(self: Box<Self>, body: hyper::Body, identity: Option<String>) -> ResponseFuture {
+        // TODO Check incoming Content-Type
+
+        use futures::Stream;
+
+        if self.to.revision - self.from.revision != 1 {
+            return Box::new(futures::finished(
+                Response::new()
+                    .with_status(hyper::StatusCode::BadRequest)
+                    .with_header(ContentType(TEXT_PLAIN.clone()))
+                    .with_body("Only the difference between consecutive revisions can be undone"),
+            ));
+        }
+
+        let state = self.state.clone();
+
+        Box::new(
+            body.concat2()
+                .map_err(Into::into)
+                .and_then(move |_| {
+                    self.state
+                        .undo_change(self.to.article_id, self.to.revision, identity)
+                })
+                .and_then(move |updated| -> ResponseFuture {
+                    match updated {
+                        UpdateResult::Success(updated) => Box::new(futures::finished(
+                            Response::new()
+                                .with_status(hyper::StatusCode::SeeOther)
+                                .with_header(ContentType(TEXT_PLAIN.clone()))
+                                .with_header(Location::new(format!("../{}", updated.link())))
+                                .with_body("See other"),
+                        )),
+                        UpdateResult::RebaseTooLong => unreachable!("There is nothing to rebase"),
+                        UpdateResult::RebaseConflict(conflict) => {
+                            let action = conflict.base_article.link().to_owned();
+                            Box::new(
+                                rebase_conflict_page(&state, Some("../"), action, conflict).map(
+                                    |page| {
+                                        Response::new()
+                                            .with_status(hyper::StatusCode::Ok)
+                                            .with_header(ContentType(TEXT_HTML.clone()))
+                                            .with_body(page)
+                                    },
+                                ),
+                            )
+                        }
+                    }
+                }),
+        )
+    }
 }
@@ -1,25 +1,45 @@
 pub mod pagination;
 
 mod about_resource;
+mod article_format;
 mod article_resource;
 mod article_revision_resource;
+mod backlinks_resource;
 mod changes_resource;
+mod delete_article_resource;
+mod deleted_article_resource;
 mod diff_resource;
 mod html_resource;
 mod new_article_resource;
+mod orphans_resource;
+mod preview_resource;
+mod properties_resource;
 mod read_only_resource;
 mod search_resource;
 mod sitemap_resource;
+mod tag_resource;
+mod tags_resource;
 mod temporary_redirect_resource;
+mod wanted_resource;
 
 pub use self::about_resource::AboutResource;
+pub use self::article_format::ArticleFormat;
 pub use self::article_resource::ArticleResource;
 pub use self::article_revision_resource::ArticleRevisionResource;
+pub use self::backlinks_resource::BacklinksResource;
 pub use self::changes_resource::{ChangesLookup, ChangesResource};
+pub use self::delete_article_resource::DeleteArticleResource;
+pub use self::deleted_article_resource::DeletedArticleResource;
 pub use self::diff_resource::{DiffLookup, DiffResource};
 pub use self::html_resource::HtmlResource;
 pub use self::new_article_resource::NewArticleResource;
+pub use self::orphans_resource::OrphansResource;
+pub use self::preview_resource::PreviewResource;
+pub use self::properties_resource::PropertiesLookup;
 pub use self::read_only_resource::ReadOnlyResource;
 pub use self::search_resource::SearchLookup;
 pub use self::sitemap_resource::SitemapResource;
+pub use self::tag_resource::TagResource;
+pub use self::tags_resource::TagsResource;
 pub use self::temporary_redirect_resource::TemporaryRedirectResource;
+pub use self::wanted_resource::WantedResource;
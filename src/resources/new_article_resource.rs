@@ -5,7 +5,7 @@ use hyper::server::*;
 
 use crate::assets::ScriptJs;
 use crate::mimes::*;
-use crate::rendering::render_markdown;
+use crate::rendering::Heading;
 use crate::site::Layout;
 use crate::state::State;
 use crate::theme::{self, Theme};
@@ -35,6 +35,7 @@ struct CreateArticle {
     title: String,
     body: String,
     theme: Option<Theme>,
+    summary: Option<String>,
 }
 
 impl NewArticleResource {
@@ -69,12 +70,16 @@ impl Resource for NewArticleResource {
         struct Template<'a> {
             revision: &'a str,
             last_updated: Option<&'a str>,
+            backlinks_link: Option<String>,
 
             edit: bool,
+            action: &'a str,
             cancel_url: Option<&'a str>,
             title: &'a str,
+            section: Option<usize>,
             raw: &'a str,
             rendered: &'a str,
+            toc: Vec<Heading>,
             themes: &'a [SelectableTheme],
         }
         impl<'a> Template<'a> {
@@ -97,11 +102,15 @@ impl Resource for NewArticleResource {
                     body: &Template {
                         revision: NEW,
                         last_updated: None,
+                        backlinks_link: None,
                         edit: self.edit,
+                        action: "",
                         cancel_url: self.slug.as_deref(),
                         title: &title,
+                        section: None,
                         raw: "",
                         rendered: EMPTY_ARTICLE_MESSAGE,
+                        toc: vec![],
                         themes: &theme::THEMES
                             .iter()
                             .map(|&x| SelectableTheme {
@@ -128,6 +137,7 @@ impl Resource for NewArticleResource {
         struct Template<'a> {
             title: &'a str,
             rendered: String,
+            toc: Vec<Heading>,
         }
 
         #[derive(Serialize)]
@@ -142,6 +152,8 @@ impl Resource for NewArticleResource {
             last_updated: &'a str,
         }
 
+        let state = self.state.clone();
+
         Box::new(
             body.concat2()
                 .map_err(Into::into)
@@ -157,35 +169,40 @@ impl Resource for NewArticleResource {
                         arg.body,
                         identity,
                         theme,
+                        arg.summary,
                     )
                 })
-                .and_then(|updated| {
-                    futures::finished(
-                        Response::new()
-                            .with_status(hyper::StatusCode::Ok)
-                            .with_header(ContentType(APPLICATION_JSON.clone()))
-                            .with_body(
-                                serde_json::to_string(&PutResponse {
-                                    slug: &updated.slug,
-                                    article_id: updated.article_id,
-                                    revision: updated.revision,
-                                    title: &updated.title,
-                                    body: &updated.body,
-                                    theme: updated.theme,
-                                    rendered: &Template {
+                .and_then(move |updated| {
+                    let toc = state.table_of_contents(&updated.body);
+                    state
+                        .render_revision(updated.article_id, updated.revision, updated.body.clone())
+                        .map(move |rendered| {
+                            Response::new()
+                                .with_status(hyper::StatusCode::Ok)
+                                .with_header(ContentType(APPLICATION_JSON.clone()))
+                                .with_body(
+                                    serde_json::to_string(&PutResponse {
+                                        slug: &updated.slug,
+                                        article_id: updated.article_id,
+                                        revision: updated.revision,
                                         title: &updated.title,
-                                        rendered: render_markdown(&updated.body),
-                                    }
-                                    .to_string(),
-                                    last_updated: &super::article_resource::last_updated(
-                                        updated.article_id,
-                                        &Local.from_utc_datetime(&updated.created),
-                                        updated.author.as_deref(),
-                                    ),
-                                })
-                                .expect("Should never fail"),
-                            ),
-                    )
+                                        body: &updated.body,
+                                        theme: updated.theme,
+                                        rendered: &Template {
+                                            title: &updated.title,
+                                            rendered,
+                                            toc,
+                                        }
+                                        .to_string(),
+                                        last_updated: &super::article_resource::last_updated(
+                                            updated.article_id,
+                                            &Local.from_utc_datetime(&updated.created),
+                                            updated.author.as_deref(),
+                                        ),
+                                    })
+                                    .expect("Should never fail"),
+                                )
+                        })
                 }),
         )
     }
@@ -211,6 +228,7 @@ impl Resource for NewArticleResource {
                         arg.body,
                         identity,
                         theme,
+                        arg.summary,
                     )
                 })
                 .and_then(|updated| {
@@ -0,0 +1,59 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::models::ArticleRevisionStub;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+pub struct OrphansResource {
+    state: State,
+}
+
+impl OrphansResource {
+    pub fn new(state: State) -> Self {
+        OrphansResource { state }
+    }
+}
+
+impl Resource for OrphansResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        #[derive(BartDisplay)]
+        #[template = "templates/orphans.html"]
+        struct Template<'a> {
+            articles: &'a [ArticleRevisionStub],
+        }
+
+        let data = self.state.get_orphans();
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(articles, head)| {
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    "Orphaned articles",
+                    Template {
+                        articles: &articles,
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
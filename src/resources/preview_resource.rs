@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use futures::{self, Future};
+
+use hyper::header::{ContentType, RetryAfter};
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::rate_limit::{Client, RateLimit};
+use crate::rendering::Heading;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+// The editor asks for a preview when the author pauses typing
+const PREVIEWS_PER_MINUTE: u32 = 60;
+
+lazy_static! {
+    static ref PREVIEW_LIMIT: RateLimit =
+        RateLimit::new(PREVIEWS_PER_MINUTE, Duration::from_secs(60));
+}
+
+#[derive(Deserialize)]
+struct PreviewArticle {
+    #[serde(default)]
+    title: String,
+    body: String,
+}
+
+pub struct PreviewResource {
+    state: State,
+    client: Client,
+}
+
+impl PreviewResource {
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            client: Client::Unknown,
+        }
+    }
+}
+
+impl Resource for PreviewResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Post]
+    }
+
+    fn post(self: Box<Self>, body: hyper::Body, _identity: Option<String>) -> ResponseFuture {
+        // TODO Check incoming Content-Type
+
+        use futures::Stream;
+
+        // Checked before reading the body, so refused requests cost nothing
+        if let Err(wait) = PREVIEW_LIMIT.check(&self.client) {
+            // Round up, so the client does not retry too early
+            let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            return Box::new(futures::finished(
+                Response::new()
+                    .with_status(hyper::StatusCode::TooManyRequests)
+                    .with_header(RetryAfter::Delay(Duration::from_secs(seconds)))
+                    .with_header(ContentType(TEXT_PLAIN.clone()))
+                    .with_body("Too many requests\n"),
+            ));
+        }
+
+        #[derive(BartDisplay)]
+        #[template = "templates/article_contents.html"]
+        struct Template<'a> {
+            title: &'a str,
+            rendered: String,
+            toc: Vec<Heading>,
+        }
+
+        Box::new(
+            body.concat2()
+                .map_err(Into::into)
+                .and_then(move |body| -> ResponseFuture {
+                    let preview: PreviewArticle = match serde_urlencoded::from_bytes(&body) {
+                        Ok(x) => x,
+                        Err(x) => return Box::new(futures::failed(x.into())),
+                    };
+
+                    let toc = self.state.table_of_contents(&preview.body);
+                    Box::new(self.state.render_markdown(None, preview.body.clone()).map(
+                        move |rendered| {
+                            Response::new()
+                                .with_status(hyper::StatusCode::Ok)
+                                .with_header(ContentType(TEXT_HTML.clone()))
+                                .with_body(
+                                    Template {
+                                        title: &preview.title,
+                                        rendered,
+                                        toc,
+                                    }
+                                    .to_string(),
+                                )
+                        },
+                    ))
+                }),
+        )
+    }
+
+    fn hacky_inject_client(&mut self, client: Client) {
+        self.client = client;
+    }
+}
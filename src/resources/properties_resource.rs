@@ -0,0 +1,140 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::models::PropertyMatch;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+type BoxResource = Box<dyn Resource + Sync + Send>;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct QueryParameters {
+    key: Option<String>,
+    value: Option<String>,
+}
+
+impl QueryParameters {
+    pub fn key(self, key: Option<String>) -> Self {
+        Self { key, ..self }
+    }
+
+    pub fn value(self, value: Option<String>) -> Self {
+        Self { value, ..self }
+    }
+
+    pub fn into_link(self) -> String {
+        let args = serde_urlencoded::to_string(self).expect("Serializing to String cannot fail");
+        if !args.is_empty() {
+            format!("_properties?{}", args)
+        } else {
+            "_properties".to_owned()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PropertiesLookup {
+    state: State,
+}
+
+impl PropertiesLookup {
+    pub fn new(state: State) -> Self {
+        Self { state }
+    }
+
+    pub fn lookup(&self, query: Option<&str>) -> Result<Option<BoxResource>, crate::web::Error> {
+        let args: QueryParameters = serde_urlencoded::from_str(query.unwrap_or(""))?;
+
+        Ok(Some(Box::new(PropertiesResource::new(
+            self.state.clone(),
+            args.key.filter(|x| !x.is_empty()),
+            args.value,
+        ))))
+    }
+}
+
+pub struct PropertiesResource {
+    state: State,
+    key: Option<String>,
+    value: Option<String>,
+}
+
+impl PropertiesResource {
+    pub fn new(state: State, key: Option<String>, value: Option<String>) -> Self {
+        Self { state, key, value }
+    }
+}
+
+impl Resource for PropertiesResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        struct Row<'a> {
+            data: &'a PropertyMatch,
+            key_link: String,
+            value_link: String,
+        }
+
+        #[derive(BartDisplay)]
+        #[template = "templates/properties.html"]
+        struct Template<'a> {
+            key: Option<&'a str>,
+            value: Option<&'a str>,
+            all_properties_link: Option<String>,
+            rows: &'a [Row<'a>],
+        }
+
+        let data = self
+            .state
+            .get_property_matches(self.key.clone(), self.value.clone());
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(data, head)| {
+            let rows = data
+                .iter()
+                .map(|x| Row {
+                    data: x,
+                    key_link: QueryParameters::default()
+                        .key(Some(x.key.clone()))
+                        .into_link(),
+                    value_link: QueryParameters::default()
+                        .key(Some(x.key.clone()))
+                        .value(Some(x.value.clone()))
+                        .into_link(),
+                })
+                .collect::<Vec<_>>();
+
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    "Properties",
+                    Template {
+                        key: self.key.as_deref(),
+                        value: self.value.as_deref(),
+                        all_properties_link: self
+                            .key
+                            .as_ref()
+                            .map(|_| QueryParameters::default().into_link()),
+                        rows: &rows,
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
@@ -2,6 +2,7 @@ use futures::{self, Future};
 
 use hyper::header::{Accept, ContentType};
 use hyper::server::*;
+use slug::slugify;
 
 use crate::mimes::*;
 use crate::models::SearchResult;
@@ -17,6 +18,7 @@ type BoxResource = Box<dyn Resource + Sync + Send>;
 #[derive(Serialize, Deserialize, Default)]
 pub struct QueryParameters {
     q: Option<String>,
+    tag: Option<String>,
     offset: Option<u32>,
     limit: Option<u32>,
     snippet_size: Option<u32>,
@@ -78,6 +80,7 @@ impl SearchLookup {
         Ok(Some(Box::new(SearchResource::new(
             self.state.clone(),
             args.q,
+            args.tag.map(slugify).filter(|x| !x.is_empty()),
             args.limit.unwrap_or(DEFAULT_LIMIT),
             args.offset.unwrap_or(0),
             args.snippet_size.unwrap_or(DEFAULT_SNIPPET_SIZE),
@@ -90,6 +93,7 @@ pub struct SearchResource {
     response_type: ResponseType,
 
     query: Option<String>,
+    tag: Option<String>,
     limit: u32,
     offset: u32,
     snippet_size: u32,
@@ -105,6 +109,7 @@ impl SearchResource {
     pub fn new(
         state: State,
         query: Option<String>,
+        tag: Option<String>,
         limit: u32,
         offset: u32,
         snippet_size: u32,
@@ -113,6 +118,7 @@ impl SearchResource {
             state,
             response_type: ResponseType::Html,
             query,
+            tag,
             limit,
             offset,
             snippet_size,
@@ -122,6 +128,7 @@ impl SearchResource {
     fn query_args(&self) -> QueryParameters {
         QueryParameters {
             q: self.query.clone(),
+            tag: self.tag.clone(),
             ..QueryParameters::default()
         }
         .offset(self.offset)
@@ -177,6 +184,7 @@ impl Resource for SearchResource {
         #[template = "templates/search.html"]
         struct Template<'a> {
             query: &'a str,
+            tag: Option<&'a str>,
             hits: &'a [(usize, &'a SearchResult)],
             prev: Option<String>,
             next: Option<String>,
@@ -191,6 +199,7 @@ impl Resource for SearchResource {
 
         let data = self.state.search_query(
             query,
+            self.tag.clone(),
             (self.limit + 1) as i32,
             self.offset as i32,
             self.snippet_size as i32,
@@ -235,6 +244,7 @@ impl Resource for SearchResource {
                         "Search",
                         &Template {
                             query: self.query.as_deref().unwrap_or(""),
+                            tag: self.tag.as_deref(),
                             hits: &data.iter().enumerate().collect::<Vec<_>>(),
                             prev,
                             next,
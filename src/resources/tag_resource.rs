@@ -0,0 +1,71 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::models::ArticleRevisionStub;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+use super::changes_resource;
+
+pub struct TagResource {
+    state: State,
+    tag: String,
+}
+
+impl TagResource {
+    pub fn new(state: State, tag: String) -> Self {
+        Self { state, tag }
+    }
+}
+
+impl Resource for TagResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        #[derive(BartDisplay)]
+        #[template = "templates/tag.html"]
+        struct Template<'a> {
+            tag: &'a str,
+            articles: &'a [ArticleRevisionStub],
+            changes_link: String,
+        }
+
+        let data = self.state.get_tagged_articles(self.tag.clone());
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(articles, head)| {
+            Ok(head.with_body(
+                system_page(
+                    Some("../"), // Hmm, should perhaps accept `base` as argument
+                    &format!("Tag: {}", self.tag),
+                    Template {
+                        tag: &self.tag,
+                        articles: &articles,
+                        changes_link: format!(
+                            "_changes{}",
+                            changes_resource::QueryParameters::default()
+                                .tag(Some(self.tag.clone()))
+                                .into_link()
+                        ),
+                    },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
@@ -0,0 +1,66 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+pub struct TagsResource {
+    state: State,
+}
+
+impl TagsResource {
+    pub fn new(state: State) -> Self {
+        Self { state }
+    }
+}
+
+impl Resource for TagsResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        struct Tag {
+            tag: String,
+            count: i64,
+        }
+
+        #[derive(BartDisplay)]
+        #[template = "templates/tags.html"]
+        struct Template<'a> {
+            tags: &'a [Tag],
+        }
+
+        let data = self.state.get_tags();
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(tags, head)| {
+            let tags = tags
+                .into_iter()
+                .map(|(tag, count)| Tag { tag, count })
+                .collect::<Vec<_>>();
+
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    "Tags",
+                    Template { tags: &tags },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
@@ -0,0 +1,81 @@
+use futures::{self, Future};
+
+use hyper::header::ContentType;
+use hyper::server::*;
+
+use crate::mimes::*;
+use crate::models::WantedLink;
+use crate::site::system_page;
+use crate::state::State;
+use crate::web::{Resource, ResponseFuture};
+
+pub struct WantedResource {
+    state: State,
+}
+
+impl WantedResource {
+    pub fn new(state: State) -> Self {
+        WantedResource { state }
+    }
+}
+
+impl Resource for WantedResource {
+    fn allow(&self) -> Vec<hyper::Method> {
+        use hyper::Method::*;
+        vec![Options, Head, Get]
+    }
+
+    fn head(&self) -> ResponseFuture {
+        Box::new(futures::finished(
+            Response::new()
+                .with_status(hyper::StatusCode::Ok)
+                .with_header(ContentType(TEXT_HTML.clone())),
+        ))
+    }
+
+    fn get(self: Box<Self>) -> ResponseFuture {
+        struct Wanted<'a> {
+            slug: &'a str,
+            count: usize,
+            sources: Vec<&'a WantedLink>,
+        }
+
+        #[derive(BartDisplay)]
+        #[template = "templates/wanted.html"]
+        struct Template<'a> {
+            wanted: &'a [Wanted<'a>],
+        }
+
+        let data = self.state.get_wanted_links();
+        let head = self.head();
+
+        Box::new(data.join(head).and_then(move |(links, head)| {
+            let mut wanted: Vec<Wanted> = vec![];
+            for link in &links {
+                match wanted.last_mut() {
+                    Some(x) if x.slug == link.target_slug => x.sources.push(link),
+                    _ => wanted.push(Wanted {
+                        slug: &link.target_slug,
+                        count: 0,
+                        sources: vec![link],
+                    }),
+                }
+            }
+            for x in &mut wanted {
+                x.count = x.sources.len();
+            }
+
+            // The most wanted articles first
+            wanted.sort_by(|a, b| b.count.cmp(&a.count).then(a.slug.cmp(b.slug)));
+
+            Ok(head.with_body(
+                system_page(
+                    None, // Hmm, should perhaps accept `base` as argument
+                    "Wanted articles",
+                    Template { wanted: &wanted },
+                )
+                .to_string(),
+            ))
+        }))
+    }
+}
@@ -1,2 +1,4 @@
 #![allow(dead_code)]
 include!(concat!(env!("OUT_DIR"), "/infer_schema.rs"));
+
+allow_tables_to_appear_in_same_query!(article_revisions, links, tags);
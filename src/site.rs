@@ -11,6 +11,7 @@ use hyper::server::*;
 
 use crate::assets::{SearchJs, StyleCss, ThemesCss};
 use crate::build_config;
+use crate::rate_limit::Client;
 use crate::theme;
 use crate::web::Lookup;
 use crate::wiki_lookup::WikiLookup;
@@ -134,6 +135,9 @@ impl Service for Site {
     type Future = Box<dyn futures::Future<Item = Response, Error = Self::Error>>;
 
     fn call(&self, req: Request) -> Self::Future {
+        // The only way to get the address of the client with `Http::bind`
+        #[allow(deprecated)]
+        let remote_addr = req.remote_addr();
         let (method, uri, _http_version, headers, body) = req.deconstruct();
 
         println!("{} {}", method, uri);
@@ -143,6 +147,12 @@ impl Service for Site {
             false => None,
         };
 
+        let client = match (&identity, remote_addr) {
+            (Some(identity), _) => Client::Identity(identity.clone()),
+            (None, Some(addr)) => Client::Address(addr.ip()),
+            (None, None) => Client::Unknown,
+        };
+
         let accept_header = headers.get().cloned().unwrap_or_else(|| Accept(vec![]));
 
         let base = root_base_from_request_uri(uri.path());
@@ -155,6 +165,7 @@ impl Service for Site {
                     Some(mut resource) => {
                         use hyper::Method::*;
                         resource.hacky_inject_accept_header(accept_header);
+                        resource.hacky_inject_client(client);
                         match method {
                             Options => Box::new(futures::finished(resource.options())),
                             Head => resource.head(),
@@ -1,4 +1,9 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use diesel::expression::SqlLiteral;
 use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable};
 use diesel::sqlite::SqliteConnection;
 use futures_cpupool::{self, CpuFuture};
 use r2d2::Pool;
@@ -6,6 +11,7 @@ use r2d2_diesel::ConnectionManager;
 
 use crate::merge;
 use crate::models;
+use crate::rendering;
 use crate::schema::*;
 use crate::theme::Theme;
 
@@ -13,13 +19,29 @@ use crate::theme::Theme;
 pub struct State {
     connection_pool: Pool<ConnectionManager<SqliteConnection>>,
     cpu_pool: futures_cpupool::CpuPool,
+    settings: Arc<Settings>,
 }
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
+// Chosen on the command line
+#[derive(Debug)]
+pub struct Settings {
+    // Edits based on revisions further behind the latest revision than this
+    // are rejected instead of rebased, to bound the work done per request
+    pub max_rebase_distance: i32,
+    // Articles with at least this many headings get a table of contents
+    pub min_toc_headings: usize,
+    // The URL schemes that links and images in articles can use, in lower
+    // case
+    pub allowed_schemes: Vec<String>,
+}
+
+#[derive(Debug)]
 pub enum SlugLookup {
     Miss,
     Hit { article_id: i32, revision: i32 },
+    Deleted { article_id: i32, revision: i32 },
     Redirect(String),
 }
 
@@ -34,6 +56,9 @@ struct NewRevision<'a> {
     author: Option<&'a str>,
     latest: bool,
     theme: Theme,
+    deleted: bool,
+    summary: Option<&'a str>,
+    minor: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -44,6 +69,15 @@ pub struct RebaseConflict {
     pub theme: Theme,
 }
 
+fn merge_themes(a: Theme, o: Theme, b: Theme) -> Theme {
+    // Last change wins
+    if a != o {
+        a
+    } else {
+        b
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum RebaseResult {
     Clean {
@@ -54,9 +88,46 @@ enum RebaseResult {
     Conflict(RebaseConflict),
 }
 
+#[derive(Debug)]
 pub enum UpdateResult {
     Success(models::ArticleRevision),
     RebaseConflict(RebaseConflict),
+    RebaseTooLong,
+}
+
+// The article that `links.target_slug` leads to, resolved the same way as
+// lookup_slug, by the most recent revision with the slug. NULL for a miss.
+fn link_target_article() -> SqlLiteral<Nullable<Integer>> {
+    diesel::dsl::sql(
+        "(SELECT target.article_id FROM article_revisions AS target \
+            WHERE target.slug = links.target_slug \
+            ORDER BY target.sequence_number DESC LIMIT 1)",
+    )
+}
+
+fn listed_article(stub: models::ArticleRevisionStub) -> rendering::ListedArticle {
+    rendering::ListedArticle {
+        link: stub.link().to_owned(),
+        title: stub.title,
+        note: None,
+    }
+}
+
+fn recent_change(stub: models::ArticleRevisionStub) -> rendering::ListedArticle {
+    use chrono::{Local, TimeZone};
+
+    let created = Local
+        .from_utc_datetime(&stub.created)
+        .format("%Y-%m-%d %H:%M");
+    let note = match &stub.author {
+        Some(author) => format!("{} by {}", created, author),
+        None => created.to_string(),
+    };
+
+    rendering::ListedArticle {
+        note: Some(note),
+        ..listed_article(stub)
+    }
 }
 
 fn decide_slug(
@@ -112,11 +183,15 @@ fn decide_slug(
 
 struct SyncState<'a> {
     db_connection: &'a diesel::SqliteConnection,
+    settings: &'a Settings,
 }
 
 impl<'a> SyncState<'a> {
-    fn new(db_connection: &diesel::SqliteConnection) -> SyncState {
-        SyncState { db_connection }
+    fn new(db_connection: &'a diesel::SqliteConnection, settings: &'a Settings) -> SyncState<'a> {
+        SyncState {
+            db_connection,
+            settings,
+        }
     }
 
     pub fn get_article_slug(&self, article_id: i32) -> Result<Option<String>, Error> {
@@ -163,6 +238,10 @@ impl<'a> SyncState<'a> {
                 latest,
                 author,
                 theme,
+                deleted,
+                restored_revision,
+                summary,
+                minor,
             ))
             .load(self.db_connection)?)
     }
@@ -188,6 +267,7 @@ impl<'a> SyncState<'a> {
             article_id: i32,
             revision: i32,
             latest: bool,
+            deleted: bool,
         }
 
         self.db_connection.transaction(|| {
@@ -199,11 +279,16 @@ impl<'a> SyncState<'a> {
                         article_revisions::article_id,
                         article_revisions::revision,
                         article_revisions::latest,
+                        article_revisions::deleted,
                     ))
                     .first::<ArticleRevisionStub>(self.db_connection)
                     .optional()?
                 {
                     None => SlugLookup::Miss,
+                    Some(ref stub) if stub.latest && stub.deleted => SlugLookup::Deleted {
+                        article_id: stub.article_id,
+                        revision: stub.revision,
+                    },
                     Some(ref stub) if stub.latest => SlugLookup::Hit {
                         article_id: stub.article_id,
                         revision: stub.revision,
@@ -233,44 +318,27 @@ impl<'a> SyncState<'a> {
         let mut body_a = body;
         let mut theme_a = theme;
 
-        // TODO: Improve this implementation.
-        // Weakness: If the range of revisions is big, _one_ request from the
-        //   client can cause _many_ database requests, cheaply causing lots
-        //   of work for the server. Possible attack vector.
-        // Weakness: When the range is larger than just one iteration, the
-        //   same title and body are retrieved from the database multiple
-        //   times. Unnecessary extra copies.
-
-        for revision in existing_base_revision..target_base_revision {
-            let mut stored = article_revisions::table
-                .filter(article_revisions::article_id.eq(article_id))
-                .filter(article_revisions::revision.ge(revision))
-                .filter(article_revisions::revision.le(revision + 1))
-                .order(article_revisions::revision.asc())
-                .select((
-                    article_revisions::title,
-                    article_revisions::body,
-                    article_revisions::theme,
-                ))
-                .load::<(String, String, Theme)>(self.db_connection)?;
+        let stored = article_revisions::table
+            .filter(article_revisions::article_id.eq(article_id))
+            .filter(article_revisions::revision.ge(existing_base_revision))
+            .filter(article_revisions::revision.le(target_base_revision))
+            .order(article_revisions::revision.asc())
+            .select((
+                article_revisions::title,
+                article_revisions::body,
+                article_revisions::theme,
+            ))
+            .load::<(String, String, Theme)>(self.db_connection)?;
 
-            let (title_b, body_b, theme_b) = stored.pop().expect("Application layer guarantee");
-            let (title_o, body_o, theme_o) = stored.pop().expect("Application layer guarantee");
+        for (revision, pair) in (existing_base_revision..).zip(stored.windows(2)) {
+            let (ref title_o, ref body_o, theme_o) = pair[0];
+            let (ref title_b, ref body_b, theme_b) = pair[1];
 
             use crate::merge::MergeResult::*;
 
-            fn merge_themes(a: Theme, o: Theme, b: Theme) -> Theme {
-                // Last change wins
-                if a != o {
-                    a
-                } else {
-                    b
-                }
-            }
-
             let update = {
-                let title_merge = merge::merge_chars(&title_a, &title_o, &title_b);
-                let body_merge = merge::merge_lines(&body_a, &body_o, &body_b);
+                let title_merge = merge::merge_chars(&title_a, title_o, title_b);
+                let body_merge = merge::merge_lines(&body_a, body_o, body_b);
                 let theme = merge_themes(theme_a, theme_o, theme_b);
 
                 match (title_merge, body_merge) {
@@ -300,6 +368,7 @@ impl<'a> SyncState<'a> {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update_article(
         &self,
         article_id: i32,
@@ -308,30 +377,95 @@ impl<'a> SyncState<'a> {
         body: String,
         author: Option<String>,
         theme: Option<Theme>,
+        summary: Option<String>,
+        minor: bool,
     ) -> Result<UpdateResult, Error> {
         if title.is_empty() {
             return Err("title cannot be empty".into());
         }
 
         self.db_connection.transaction(|| {
-            let (latest_revision, prev_title, prev_slug, prev_theme) = article_revisions::table
-                .filter(article_revisions::article_id.eq(article_id))
-                .order(article_revisions::revision.desc())
-                .select((
-                    article_revisions::revision,
-                    article_revisions::title,
-                    article_revisions::slug,
-                    article_revisions::theme,
-                ))
-                .first::<(i32, String, String, Theme)>(self.db_connection)?;
-
-            // TODO: If this is an historic edit repeated, just respond OK
-            // This scheme would make POST idempotent.
+            let (latest_revision, prev_title, prev_slug, prev_theme, prev_deleted) =
+                article_revisions::table
+                    .filter(article_revisions::article_id.eq(article_id))
+                    .order(article_revisions::revision.desc())
+                    .select((
+                        article_revisions::revision,
+                        article_revisions::title,
+                        article_revisions::slug,
+                        article_revisions::theme,
+                        article_revisions::deleted,
+                    ))
+                    .first::<(i32, String, String, Theme, bool)>(self.db_connection)?;
 
             if base_revision > latest_revision {
                 return Err("This edit is based on a future version of the article".into());
             }
 
+            if base_revision < latest_revision {
+                // If this is an historic edit repeated, respond with the
+                // revision it created. This makes POST and PUT idempotent.
+                // The edit may have been rebased when it was made, so it is
+                // rebased the same way before comparing.
+                let candidates = article_revisions::table
+                    .filter(article_revisions::article_id.eq(article_id))
+                    .filter(article_revisions::revision.gt(base_revision))
+                    .filter(
+                        article_revisions::revision
+                            .le(base_revision + 1 + self.settings.max_rebase_distance),
+                    )
+                    .filter(article_revisions::deleted.eq(false))
+                    .filter(article_revisions::minor.eq(minor))
+                    .order(article_revisions::revision.asc())
+                    .load::<models::ArticleRevision>(self.db_connection)?;
+
+                let summary = summary.as_deref().filter(|x| !x.is_empty());
+                for candidate in candidates {
+                    if candidate.author != author || candidate.summary.as_deref() != summary {
+                        continue;
+                    }
+
+                    let prev_theme = match article_revisions::table
+                        .filter(article_revisions::article_id.eq(article_id))
+                        .filter(article_revisions::revision.eq(candidate.revision - 1))
+                        .select(article_revisions::theme)
+                        .first::<Theme>(self.db_connection)
+                        .optional()?
+                    {
+                        Some(x) => x,
+                        None => continue,
+                    };
+
+                    let rebased = self.rebase_update(
+                        article_id,
+                        candidate.revision - 1,
+                        base_revision,
+                        title.clone(),
+                        body.clone(),
+                        theme.unwrap_or(prev_theme),
+                    )?;
+                    if rebased
+                        == (RebaseResult::Clean {
+                            title: candidate.title.clone(),
+                            body: candidate.body.clone(),
+                            theme: candidate.theme,
+                        })
+                    {
+                        return Ok(UpdateResult::Success(candidate));
+                    }
+                }
+            }
+
+            // Edits, reverts and undos must not bring back a deleted article
+            // on the side. Only undelete_article does that.
+            if prev_deleted {
+                return Err("Cannot change a deleted article".into());
+            }
+
+            if latest_revision - base_revision > self.settings.max_rebase_distance {
+                return Ok(UpdateResult::RebaseTooLong);
+            }
+
             let theme = theme.unwrap_or(prev_theme);
             let rebase_result = self.rebase_update(
                 article_id,
@@ -375,9 +509,16 @@ impl<'a> SyncState<'a> {
                     author: author.as_deref(),
                     latest: true,
                     theme,
+                    deleted: false,
+                    summary: summary.as_deref().filter(|x| !x.is_empty()),
+                    minor,
                 })
                 .execute(self.db_connection)?;
 
+            self.update_links(article_id, &body)?;
+            self.update_tags(article_id, &body)?;
+            self.update_properties(article_id, &body)?;
+
             Ok(UpdateResult::Success(
                 article_revisions::table
                     .filter(article_revisions::article_id.eq(article_id))
@@ -387,6 +528,42 @@ impl<'a> SyncState<'a> {
         })
     }
 
+    // Section edits are spliced into the revision they are based on, so the
+    // full body goes through the regular merge in update_article
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_article_section(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        section: usize,
+        title: String,
+        body: String,
+        author: Option<String>,
+        theme: Option<Theme>,
+        summary: Option<String>,
+        minor: bool,
+    ) -> Result<UpdateResult, Error> {
+        self.db_connection.transaction(|| {
+            let base = self
+                .get_article_revision(article_id, base_revision)?
+                .ok_or("This edit is based on a version of the article that does not exist")?;
+
+            let body = rendering::replace_section(&base.body, section, &body)
+                .ok_or("The section does not exist in this version of the article")?;
+
+            self.update_article(
+                article_id,
+                base_revision,
+                title,
+                body,
+                author,
+                theme,
+                summary,
+                minor,
+            )
+        })
+    }
+
     pub fn create_article(
         &self,
         target_slug: Option<String>,
@@ -394,6 +571,7 @@ impl<'a> SyncState<'a> {
         body: String,
         author: Option<String>,
         theme: Theme,
+        summary: Option<String>,
     ) -> Result<models::ArticleRevision, Error> {
         if title.is_empty() {
             return Err("title cannot be empty".into());
@@ -437,9 +615,16 @@ impl<'a> SyncState<'a> {
                     author: author.as_deref(),
                     latest: true,
                     theme,
+                    deleted: false,
+                    summary: summary.as_deref().filter(|x| !x.is_empty()),
+                    minor: false,
                 })
                 .execute(self.db_connection)?;
 
+            self.update_links(article_id, &body)?;
+            self.update_tags(article_id, &body)?;
+            self.update_properties(article_id, &body)?;
+
             Ok(article_revisions::table
                 .filter(article_revisions::article_id.eq(article_id))
                 .filter(article_revisions::revision.eq(new_revision))
@@ -447,516 +632,2602 @@ impl<'a> SyncState<'a> {
         })
     }
 
-    pub fn search_query(
+    pub fn revert_article(
         &self,
-        query_string: String,
-        limit: i32,
-        offset: i32,
-        snippet_size: i32,
-    ) -> Result<Vec<models::SearchResult>, Error> {
-        use diesel::sql_query;
-        use diesel::sql_types::{Integer, Text};
+        article_id: i32,
+        revision: i32,
+        author: Option<String>,
+    ) -> Result<models::ArticleRevision, Error> {
+        self.db_connection.transaction(|| {
+            let restore = self
+                .get_article_revision(article_id, revision)?
+                .ok_or("Cannot revert to a revision that does not exist")?;
 
-        fn fts_quote(src: &str) -> String {
-            format!("\"{}\"", src.replace('\"', "\"\""))
-        }
+            if restore.deleted {
+                return Err("Cannot revert to a deleted revision".into());
+            }
 
-        let words = query_string
-            .split_whitespace()
-            .map(fts_quote)
-            .collect::<Vec<_>>();
+            let latest_revision = article_revisions::table
+                .filter(article_revisions::article_id.eq(article_id))
+                .filter(article_revisions::latest.eq(true))
+                .select(article_revisions::revision)
+                .first::<i32>(self.db_connection)?;
 
-        let query = if words.len() > 1 {
-            format!("NEAR({})", words.join(" "))
-        } else if words.len() == 1 {
-            format!("{}*", words[0])
-        } else {
-            "\"\"".to_owned()
-        };
+            // Basing the update on the latest revision means there is nothing
+            // to rebase, so the update always succeeds
+            let reverted = match self.update_article(
+                article_id,
+                latest_revision,
+                restore.title,
+                restore.body,
+                author,
+                Some(restore.theme),
+                None,
+                false,
+            )? {
+                UpdateResult::Success(x) => x,
+                UpdateResult::RebaseConflict(_) | UpdateResult::RebaseTooLong => {
+                    unreachable!("There is nothing to rebase")
+                }
+            };
 
-        Ok(
-            sql_query(
-                "SELECT title, snippet(article_search, 1, '<em>', '</em>', '\u{2026}', ?) AS snippet, slug \
-                    FROM article_search \
-                    WHERE article_search MATCH ? \
-                    ORDER BY rank \
-                    LIMIT ? OFFSET ?"
+            diesel::update(
+                article_revisions::table
+                    .filter(article_revisions::article_id.eq(article_id))
+                    .filter(article_revisions::revision.eq(reverted.revision)),
             )
-            .bind::<Integer, _>(snippet_size)
-            .bind::<Text, _>(query)
-            .bind::<Integer, _>(limit)
-            .bind::<Integer, _>(offset)
-            .load(self.db_connection)?)
+            .set(article_revisions::restored_revision.eq(Some(revision)))
+            .execute(self.db_connection)?;
+
+            Ok(models::ArticleRevision {
+                restored_revision: Some(revision),
+                ..reverted
+            })
+        })
     }
-}
 
-impl State {
-    pub fn new(
-        connection_pool: Pool<ConnectionManager<SqliteConnection>>,
-        cpu_pool: futures_cpupool::CpuPool,
-    ) -> State {
-        State {
-            connection_pool,
-            cpu_pool,
+    pub fn undo_change(
+        &self,
+        article_id: i32,
+        revision: i32,
+        author: Option<String>,
+    ) -> Result<UpdateResult, Error> {
+        self.db_connection.transaction(|| {
+            let mut stored = article_revisions::table
+                .filter(article_revisions::article_id.eq(article_id))
+                .filter(article_revisions::revision.ge(revision - 1))
+                .filter(article_revisions::revision.le(revision))
+                .order(article_revisions::revision.asc())
+                .select((
+                    article_revisions::title,
+                    article_revisions::body,
+                    article_revisions::theme,
+                    article_revisions::deleted,
+                ))
+                .load::<(String, String, Theme, bool)>(self.db_connection)?;
+
+            if stored.len() != 2 {
+                return Err("Cannot undo a change that does not exist".into());
+            }
+
+            // Undoing the change means merging the difference from the
+            // changed revision back to the one before it with the latest
+            // revision. The undo takes the place of "your changes".
+            let (title_o, body_o, theme_o, deleted_o) = stored.pop().unwrap();
+            let (title_b, body_b, theme_b, deleted_b) = stored.pop().unwrap();
+
+            if deleted_o || deleted_b {
+                return Err("Cannot undo deleting or undeleting an article".into());
+            }
+
+            let (latest_revision, title_a, body_a, theme_a, deleted_a) = article_revisions::table
+                .filter(article_revisions::article_id.eq(article_id))
+                .filter(article_revisions::latest.eq(true))
+                .select((
+                    article_revisions::revision,
+                    article_revisions::title,
+                    article_revisions::body,
+                    article_revisions::theme,
+                    article_revisions::deleted,
+                ))
+                .first::<(i32, String, String, Theme, bool)>(self.db_connection)?;
+
+            if deleted_a {
+                return Err("Cannot undo a change to a deleted article".into());
+            }
+
+            use crate::merge::MergeResult::*;
+
+            let title_merge = merge::merge_chars(&title_b, &title_o, &title_a);
+            let body_merge = merge::merge_lines(&body_b, &body_o, &body_a);
+            let theme = merge_themes(theme_a, theme_o, theme_b);
+
+            match (title_merge, body_merge) {
+                (Clean(title), Clean(body)) => self.update_article(
+                    article_id,
+                    latest_revision,
+                    title,
+                    body,
+                    author,
+                    Some(theme),
+                    None,
+                    false,
+                ),
+                (title_merge, body_merge) => Ok(UpdateResult::RebaseConflict(RebaseConflict {
+                    base_article: self
+                        .get_article_revision_stub(article_id, latest_revision)?
+                        .expect("Application layer guarantee"),
+                    title: title_merge,
+                    body: body_merge.into_strings(),
+                    theme,
+                })),
+            }
+        })
+    }
+
+    fn update_links(&self, article_id: i32, body: &str) -> Result<(), Error> {
+        #[derive(Insertable)]
+        #[table_name = "links"]
+        struct NewLink<'a> {
+            source_article_id: i32,
+            target_slug: &'a str,
         }
+
+        diesel::delete(links::table.filter(links::source_article_id.eq(article_id)))
+            .execute(self.db_connection)?;
+
+        let targets = rendering::link_targets(body);
+        let new_links = targets
+            .iter()
+            .map(|target_slug| NewLink {
+                source_article_id: article_id,
+                target_slug,
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(links::table)
+            .values(&new_links)
+            .execute(self.db_connection)?;
+
+        Ok(())
     }
 
-    fn execute<F, T>(&self, f: F) -> CpuFuture<T, Error>
-    where
-        F: 'static + Sync + Send,
-        for<'a> F: FnOnce(SyncState<'a>) -> Result<T, Error>,
-        T: 'static + Send,
-    {
-        let connection_pool = self.connection_pool.clone();
+    fn update_tags(&self, article_id: i32, body: &str) -> Result<(), Error> {
+        #[derive(Insertable)]
+        #[table_name = "tags"]
+        struct NewTag<'a> {
+            article_id: i32,
+            tag: &'a str,
+        }
 
-        self.cpu_pool.spawn_fn(move || {
-            let db_connection = connection_pool.get()?;
+        diesel::delete(tags::table.filter(tags::article_id.eq(article_id)))
+            .execute(self.db_connection)?;
 
-            f(SyncState::new(&*db_connection))
-        })
+        let article_tags = rendering::tags(body);
+        let new_tags = article_tags
+            .iter()
+            .map(|tag| NewTag { article_id, tag })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(tags::table)
+            .values(&new_tags)
+            .execute(self.db_connection)?;
+
+        Ok(())
     }
 
-    pub fn get_article_slug(&self, article_id: i32) -> CpuFuture<Option<String>, Error> {
-        self.execute(move |state| state.get_article_slug(article_id))
+    fn update_properties(&self, article_id: i32, body: &str) -> Result<(), Error> {
+        #[derive(Insertable)]
+        #[table_name = "properties"]
+        struct NewProperty<'a> {
+            article_id: i32,
+            key: &'a str,
+            value: &'a str,
+        }
+
+        diesel::delete(properties::table.filter(properties::article_id.eq(article_id)))
+            .execute(self.db_connection)?;
+
+        let article_properties = rendering::properties(body);
+        let new_properties = article_properties
+            .iter()
+            .map(|(key, value)| NewProperty {
+                article_id,
+                key,
+                value,
+            })
+            .collect::<Vec<_>>();
+
+        diesel::insert_into(properties::table)
+            .values(&new_properties)
+            .execute(self.db_connection)?;
+
+        Ok(())
     }
 
-    pub fn get_article_revision(
+    pub fn get_property_matches(
         &self,
-        article_id: i32,
-        revision: i32,
-    ) -> CpuFuture<Option<models::ArticleRevision>, Error> {
-        self.execute(move |state| state.get_article_revision(article_id, revision))
+        key: Option<String>,
+        value: Option<String>,
+    ) -> Result<Vec<models::PropertyMatch>, Error> {
+        use diesel::sql_query;
+        use diesel::sql_types::{Nullable, Text};
+
+        Ok(sql_query(
+            "SELECT properties.key, properties.value, article_revisions.title, article_revisions.slug \
+                FROM properties \
+                JOIN article_revisions ON article_revisions.article_id = properties.article_id \
+                WHERE article_revisions.latest = 1 \
+                AND (? IS NULL OR properties.key = ?) \
+                AND (? IS NULL OR properties.value = ?) \
+                ORDER BY properties.key, properties.value, article_revisions.title",
+        )
+        .bind::<Nullable<Text>, _>(key.clone())
+        .bind::<Nullable<Text>, _>(key)
+        .bind::<Nullable<Text>, _>(value.clone())
+        .bind::<Nullable<Text>, _>(value)
+        .load(self.db_connection)?)
     }
 
-    pub fn query_article_revision_stubs<F>(
-        &self,
-        f: F,
-    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error>
-    where
-        F: 'static + Send + Sync,
-        for<'a> F: FnOnce(
-            article_revisions::BoxedQuery<'a, diesel::sqlite::Sqlite>,
-        ) -> article_revisions::BoxedQuery<'a, diesel::sqlite::Sqlite>,
-    {
-        self.execute(move |state| state.query_article_revision_stubs(f))
+    pub fn get_tags(&self) -> Result<Vec<(String, i64)>, Error> {
+        let mut counts: Vec<(String, i64)> = vec![];
+        for tag in tags::table
+            .select(tags::tag)
+            .order(tags::tag.asc())
+            .load::<String>(self.db_connection)?
+        {
+            match counts.last_mut() {
+                Some((x, count)) if *x == tag => *count += 1,
+                _ => counts.push((tag, 1)),
+            }
+        }
+
+        Ok(counts)
     }
 
     pub fn get_latest_article_revision_stubs(
         &self,
-    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error> {
+    ) -> Result<Vec<models::ArticleRevisionStub>, Error> {
         self.query_article_revision_stubs(|query| {
             query
                 .filter(article_revisions::latest.eq(true))
+                .filter(article_revisions::deleted.eq(false))
                 .order(article_revisions::title.asc())
         })
     }
 
-    pub fn lookup_slug(&self, slug: String) -> CpuFuture<SlugLookup, Error> {
-        self.execute(move |state| state.lookup_slug(slug))
+    pub fn get_tagged_articles(
+        &self,
+        tag: String,
+    ) -> Result<Vec<models::ArticleRevisionStub>, Error> {
+        let tagged = tags::table
+            .filter(tags::tag.eq(tag))
+            .select(tags::article_id);
+
+        self.query_article_revision_stubs(move |query| {
+            query
+                .filter(article_revisions::latest.eq(true))
+                .filter(article_revisions::article_id.eq_any(tagged))
+                .order(article_revisions::title.asc())
+        })
     }
 
-    pub fn update_article(
+    pub fn get_backlinks(
         &self,
         article_id: i32,
-        base_revision: i32,
-        title: String,
-        body: String,
+    ) -> Result<Vec<models::ArticleRevisionStub>, Error> {
+        // Links to earlier slugs of the article still lead to it, unless
+        // the slug has since been taken by another article
+        let sources = links::table
+            .filter(link_target_article().eq(article_id))
+            .select(links::source_article_id);
+
+        self.query_article_revision_stubs(move |query| {
+            query
+                .filter(article_revisions::latest.eq(true))
+                .filter(article_revisions::deleted.eq(false))
+                .filter(article_revisions::article_id.ne(article_id))
+                .filter(article_revisions::article_id.eq_any(sources))
+                .order(article_revisions::title.asc())
+        })
+    }
+
+    fn set_deleted(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        author: Option<String>,
+        deleted: bool,
+    ) -> Result<models::ArticleRevision, Error> {
+        self.db_connection.transaction(|| {
+            let latest = article_revisions::table
+                .filter(article_revisions::article_id.eq(article_id))
+                .filter(article_revisions::latest.eq(true))
+                .first::<models::ArticleRevision>(self.db_connection)?;
+
+            if base_revision != latest.revision {
+                return Err("The article has been changed since this request was made".into());
+            }
+
+            if latest.deleted == deleted {
+                return Err(match deleted {
+                    true => "The article is already deleted".into(),
+                    false => "The article is not deleted".into(),
+                });
+            }
+
+            let new_revision = latest.revision + 1;
+
+            diesel::update(
+                article_revisions::table
+                    .filter(article_revisions::article_id.eq(article_id))
+                    .filter(article_revisions::revision.eq(latest.revision)),
+            )
+            .set(article_revisions::latest.eq(false))
+            .execute(self.db_connection)?;
+
+            // The tombstone keeps the contents of the deleted revision, so
+            // undeleting is a matter of making another copy
+            diesel::insert_into(article_revisions::table)
+                .values(&NewRevision {
+                    article_id,
+                    revision: new_revision,
+                    slug: &latest.slug,
+                    title: &latest.title,
+                    body: &latest.body,
+                    author: author.as_deref(),
+                    latest: true,
+                    theme: latest.theme,
+                    deleted,
+                    summary: None,
+                    minor: false,
+                })
+                .execute(self.db_connection)?;
+
+            self.update_links(article_id, if deleted { "" } else { &latest.body })?;
+            self.update_tags(article_id, if deleted { "" } else { &latest.body })?;
+            self.update_properties(article_id, if deleted { "" } else { &latest.body })?;
+
+            Ok(article_revisions::table
+                .filter(article_revisions::article_id.eq(article_id))
+                .filter(article_revisions::revision.eq(new_revision))
+                .first::<models::ArticleRevision>(self.db_connection)?)
+        })
+    }
+
+    pub fn delete_article(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        author: Option<String>,
+    ) -> Result<models::ArticleRevision, Error> {
+        self.set_deleted(article_id, base_revision, author, true)
+    }
+
+    pub fn undelete_article(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        author: Option<String>,
+    ) -> Result<models::ArticleRevision, Error> {
+        self.set_deleted(article_id, base_revision, author, false)
+    }
+
+    // `article_id` is the article being rendered, if any, so it cannot
+    // include itself and `{{children}}` can find its children
+    pub fn render_markdown(&self, article_id: Option<i32>, src: &str) -> Result<String, Error> {
+        self.db_connection.transaction(|| {
+            let mut stack = article_id.into_iter().collect::<Vec<_>>();
+            let mut includes = 0;
+            Ok(rendering::render_markdown(
+                src,
+                &self.missing_link_targets(src)?,
+                &self.expansions(src, &mut stack, &mut includes)?,
+                &self.settings.allowed_schemes,
+            ))
+        })
+    }
+
+    // Revisions never change, so they are rendered once and cached. The cache
+    // is cleared when the set of existing articles changes. Articles with
+    // includes or macros depend on other articles, and are not cached.
+    pub fn render_revision(
+        &self,
+        article_id: i32,
+        revision: i32,
+        src: &str,
+    ) -> Result<String, Error> {
+        #[derive(Insertable)]
+        #[table_name = "render_cache"]
+        struct NewRenderCache<'a> {
+            article_id: i32,
+            revision: i32,
+            renderer_version: &'a str,
+            html: &'a str,
+        }
+
+        // The allowed URL schemes can change between runs, and change the
+        // output as much as a new renderer does
+        let renderer_version = format!(
+            "{}/{}",
+            rendering::RENDERER_VERSION,
+            self.settings.allowed_schemes.join(",")
+        );
+
+        let cached = render_cache::table
+            .filter(render_cache::article_id.eq(article_id))
+            .filter(render_cache::revision.eq(revision))
+            .filter(render_cache::renderer_version.eq(&renderer_version))
+            .select(render_cache::html)
+            .first::<String>(self.db_connection)
+            .optional()?;
+        if let Some(html) = cached {
+            return Ok(html);
+        }
+
+        let html = self.render_markdown(Some(article_id), src)?;
+
+        if rendering::directives(src).is_empty() {
+            // Failing to fill the cache, as when the database is busy, only
+            // means rendering again next time
+            let _ = diesel::replace_into(render_cache::table)
+                .values(&NewRenderCache {
+                    article_id,
+                    revision,
+                    renderer_version: &renderer_version,
+                    html: &html,
+                })
+                .execute(self.db_connection);
+        }
+
+        Ok(html)
+    }
+
+    fn missing_link_targets(&self, src: &str) -> Result<HashSet<String>, Error> {
+        let mut missing = HashSet::new();
+        for target in rendering::link_targets(src) {
+            match self.lookup_slug(target.clone())? {
+                SlugLookup::Miss | SlugLookup::Deleted { .. } => {
+                    missing.insert(target);
+                }
+                SlugLookup::Hit { .. } | SlugLookup::Redirect(_) => {}
+            }
+        }
+
+        Ok(missing)
+    }
+
+    // Expands the directives in `src`. `stack` holds the articles that are
+    // currently being rendered, outermost first.
+    fn expansions(
+        &self,
+        src: &str,
+        stack: &mut Vec<i32>,
+        includes: &mut usize,
+    ) -> Result<HashMap<rendering::Directive, rendering::Expansion>, Error> {
+        use rendering::{Directive, Expansion};
+
+        let mut expansions = HashMap::new();
+
+        for directive in rendering::directives(src) {
+            let expansion = match &directive {
+                Directive::Include(slug) => self.inclusion(slug.clone(), stack, includes)?,
+                &Directive::RecentChanges { limit } => Expansion::Articles(
+                    self.query_article_revision_stubs(move |query| {
+                        query
+                            .order(article_revisions::sequence_number.desc())
+                            .limit(limit as i64)
+                    })?
+                    .into_iter()
+                    .map(recent_change)
+                    .collect(),
+                ),
+                Directive::ArticleList { tag } => Expansion::Articles(
+                    match tag {
+                        Some(tag) => self.get_tagged_articles(tag.clone())?,
+                        None => self.get_latest_article_revision_stubs()?,
+                    }
+                    .into_iter()
+                    .map(listed_article)
+                    .collect(),
+                ),
+                &Directive::Search { ref query, limit } => Expansion::Articles(
+                    self.search_query(query.clone(), None, limit, 0, 1)?
+                        .into_iter()
+                        .map(|x| rendering::ListedArticle {
+                            link: x.link().to_owned(),
+                            title: x.title,
+                            note: None,
+                        })
+                        .collect(),
+                ),
+                Directive::Children => Expansion::Articles(match stack.last() {
+                    Some(&article_id) => self.get_children(article_id)?,
+                    None => vec![],
+                }),
+            };
+
+            expansions.insert(directive, expansion);
+        }
+
+        Ok(expansions)
+    }
+
+    // `includes` counts the articles included so far, at any depth, which
+    // bounds the work of rendering an article that is never cached
+    fn inclusion(
+        &self,
+        slug: String,
+        stack: &mut Vec<i32>,
+        includes: &mut usize,
+    ) -> Result<rendering::Expansion, Error> {
+        use rendering::Expansion;
+
+        let lookup = match self.lookup_slug(slug)? {
+            SlugLookup::Redirect(new_slug) => self.lookup_slug(new_slug)?,
+            x => x,
+        };
+
+        let (article_id, revision) = match lookup {
+            SlugLookup::Hit {
+                article_id,
+                revision,
+            } => (article_id, revision),
+            _ => return Ok(Expansion::Missing),
+        };
+
+        if stack.contains(&article_id) {
+            return Ok(Expansion::Cycle);
+        }
+        if stack.len() >= rendering::MAX_INCLUDE_DEPTH {
+            return Ok(Expansion::TooDeep);
+        }
+        if *includes >= rendering::MAX_INCLUDES {
+            return Ok(Expansion::TooMany);
+        }
+        *includes += 1;
+
+        let body = self
+            .get_article_revision(article_id, revision)?
+            .ok_or("The included article does not exist")?
+            .body;
+
+        stack.push(article_id);
+        let nested = self.expansions(&body, stack, includes);
+        stack.pop();
+
+        Ok(Expansion::Rendered(rendering::render_body(
+            &body,
+            &self.missing_link_targets(&body)?,
+            &nested?,
+            &self.settings.allowed_schemes,
+        )))
+    }
+
+    // The articles that have the current title or slug of the given article
+    // as their parent property
+    fn get_children(&self, article_id: i32) -> Result<Vec<rendering::ListedArticle>, Error> {
+        let slug = self
+            .get_article_slug(article_id)?
+            .ok_or("The article does not exist")?;
+
+        let mut children = self
+            .get_property_matches(Some(rendering::PARENT_PROPERTY.to_owned()), None)?
+            .into_iter()
+            .filter(|x| ::slug::slugify(&x.value) == slug)
+            .map(|x| rendering::ListedArticle {
+                link: x.link().to_owned(),
+                title: x.title,
+                note: None,
+            })
+            .collect::<Vec<_>>();
+        children.sort_by(|a, b| a.title.cmp(&b.title));
+
+        Ok(children)
+    }
+
+    pub fn get_orphans(&self) -> Result<Vec<models::ArticleRevisionStub>, Error> {
+        // Links from an article to itself do not count. Misses are NULL, and
+        // are left out by the comparison, so they do not spoil the NOT IN.
+        let linked = links::table
+            .filter(link_target_article().ne(links::source_article_id.nullable()))
+            .select(link_target_article());
+
+        self.query_article_revision_stubs(move |query| {
+            query
+                .filter(article_revisions::latest.eq(true))
+                .filter(article_revisions::deleted.eq(false))
+                .filter(article_revisions::slug.ne(""))
+                .filter(article_revisions::article_id.nullable().ne_all(linked))
+                .order(article_revisions::title.asc())
+        })
+    }
+
+    pub fn get_wanted_links(&self) -> Result<Vec<models::WantedLink>, Error> {
+        use diesel::sql_query;
+
+        // A slug that has never been used by any revision is a miss in
+        // lookup_slug
+        Ok(sql_query(
+            "SELECT links.target_slug AS target_slug, article_revisions.title AS title, \
+                    article_revisions.slug AS slug \
+                FROM links \
+                JOIN article_revisions \
+                    ON article_revisions.article_id = links.source_article_id \
+                    AND article_revisions.latest = 1 \
+                WHERE links.target_slug NOT IN (SELECT slug FROM article_revisions) \
+                ORDER BY links.target_slug, article_revisions.title",
+        )
+        .load(self.db_connection)?)
+    }
+
+    pub fn search_query(
+        &self,
+        query_string: String,
+        tag: Option<String>,
+        limit: i32,
+        offset: i32,
+        snippet_size: i32,
+    ) -> Result<Vec<models::SearchResult>, Error> {
+        use diesel::sql_query;
+        use diesel::sql_types::{Integer, Nullable, Text};
+
+        fn fts_quote(src: &str) -> String {
+            format!("\"{}\"", src.replace('\"', "\"\""))
+        }
+
+        let words = query_string
+            .split_whitespace()
+            .map(fts_quote)
+            .collect::<Vec<_>>();
+
+        let query = if words.len() > 1 {
+            format!("NEAR({})", words.join(" "))
+        } else if words.len() == 1 {
+            format!("{}*", words[0])
+        } else {
+            "\"\"".to_owned()
+        };
+
+        Ok(
+            sql_query(
+                "SELECT title, snippet(article_search, 1, '<em>', '</em>', '\u{2026}', ?) AS snippet, slug \
+                    FROM article_search \
+                    WHERE article_search MATCH ? \
+                    AND (? IS NULL OR rowid IN (SELECT article_id FROM tags WHERE tag = ?)) \
+                    ORDER BY rank \
+                    LIMIT ? OFFSET ?"
+            )
+            .bind::<Integer, _>(snippet_size)
+            .bind::<Text, _>(query)
+            .bind::<Nullable<Text>, _>(tag.clone())
+            .bind::<Nullable<Text>, _>(tag)
+            .bind::<Integer, _>(limit)
+            .bind::<Integer, _>(offset)
+            .load(self.db_connection)?)
+    }
+}
+
+impl State {
+    pub fn new(
+        connection_pool: Pool<ConnectionManager<SqliteConnection>>,
+        cpu_pool: futures_cpupool::CpuPool,
+        settings: Settings,
+    ) -> State {
+        State {
+            connection_pool,
+            cpu_pool,
+            settings: Arc::new(settings),
+        }
+    }
+
+    fn execute<F, T>(&self, f: F) -> CpuFuture<T, Error>
+    where
+        F: 'static + Sync + Send,
+        for<'a> F: FnOnce(SyncState<'a>) -> Result<T, Error>,
+        T: 'static + Send,
+    {
+        let connection_pool = self.connection_pool.clone();
+        let settings = self.settings.clone();
+
+        self.cpu_pool.spawn_fn(move || {
+            let db_connection = connection_pool.get()?;
+
+            f(SyncState::new(&*db_connection, &settings))
+        })
+    }
+
+    pub fn table_of_contents(&self, src: &str) -> Vec<rendering::Heading> {
+        rendering::table_of_contents(src, self.settings.min_toc_headings)
+    }
+
+    pub fn get_article_slug(&self, article_id: i32) -> CpuFuture<Option<String>, Error> {
+        self.execute(move |state| state.get_article_slug(article_id))
+    }
+
+    pub fn get_article_revision(
+        &self,
+        article_id: i32,
+        revision: i32,
+    ) -> CpuFuture<Option<models::ArticleRevision>, Error> {
+        self.execute(move |state| state.get_article_revision(article_id, revision))
+    }
+
+    pub fn query_article_revision_stubs<F>(
+        &self,
+        f: F,
+    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error>
+    where
+        F: 'static + Send + Sync,
+        for<'a> F: FnOnce(
+            article_revisions::BoxedQuery<'a, diesel::sqlite::Sqlite>,
+        ) -> article_revisions::BoxedQuery<'a, diesel::sqlite::Sqlite>,
+    {
+        self.execute(move |state| state.query_article_revision_stubs(f))
+    }
+
+    pub fn get_latest_article_revision_stubs(
+        &self,
+    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error> {
+        self.execute(|state| state.get_latest_article_revision_stubs())
+    }
+
+    pub fn lookup_slug(&self, slug: String) -> CpuFuture<SlugLookup, Error> {
+        self.execute(move |state| state.lookup_slug(slug))
+    }
+
+    pub fn get_backlinks(
+        &self,
+        article_id: i32,
+    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error> {
+        self.execute(move |state| state.get_backlinks(article_id))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_article(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        title: String,
+        body: String,
         author: Option<String>,
         theme: Option<Theme>,
+        summary: Option<String>,
+        minor: bool,
     ) -> CpuFuture<UpdateResult, Error> {
         self.execute(move |state| {
-            state.update_article(article_id, base_revision, title, body, author, theme)
+            state.update_article(
+                article_id,
+                base_revision,
+                title,
+                body,
+                author,
+                theme,
+                summary,
+                minor,
+            )
         })
     }
 
-    pub fn create_article(
-        &self,
-        target_slug: Option<String>,
-        title: String,
-        body: String,
-        author: Option<String>,
-        theme: Theme,
-    ) -> CpuFuture<models::ArticleRevision, Error> {
-        self.execute(move |state| state.create_article(target_slug, title, body, author, theme))
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_article_section(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        section: usize,
+        title: String,
+        body: String,
+        author: Option<String>,
+        theme: Option<Theme>,
+        summary: Option<String>,
+        minor: bool,
+    ) -> CpuFuture<UpdateResult, Error> {
+        self.execute(move |state| {
+            state.update_article_section(
+                article_id,
+                base_revision,
+                section,
+                title,
+                body,
+                author,
+                theme,
+                summary,
+                minor,
+            )
+        })
+    }
+
+    pub fn create_article(
+        &self,
+        target_slug: Option<String>,
+        title: String,
+        body: String,
+        author: Option<String>,
+        theme: Theme,
+        summary: Option<String>,
+    ) -> CpuFuture<models::ArticleRevision, Error> {
+        self.execute(move |state| {
+            state.create_article(target_slug, title, body, author, theme, summary)
+        })
+    }
+
+    pub fn revert_article(
+        &self,
+        article_id: i32,
+        revision: i32,
+        author: Option<String>,
+    ) -> CpuFuture<models::ArticleRevision, Error> {
+        self.execute(move |state| state.revert_article(article_id, revision, author))
+    }
+
+    pub fn undo_change(
+        &self,
+        article_id: i32,
+        revision: i32,
+        author: Option<String>,
+    ) -> CpuFuture<UpdateResult, Error> {
+        self.execute(move |state| state.undo_change(article_id, revision, author))
+    }
+
+    pub fn delete_article(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        author: Option<String>,
+    ) -> CpuFuture<models::ArticleRevision, Error> {
+        self.execute(move |state| state.delete_article(article_id, base_revision, author))
+    }
+
+    pub fn undelete_article(
+        &self,
+        article_id: i32,
+        base_revision: i32,
+        author: Option<String>,
+    ) -> CpuFuture<models::ArticleRevision, Error> {
+        self.execute(move |state| state.undelete_article(article_id, base_revision, author))
+    }
+
+    pub fn render_markdown(
+        &self,
+        article_id: Option<i32>,
+        src: String,
+    ) -> CpuFuture<String, Error> {
+        self.execute(move |state| state.render_markdown(article_id, &src))
+    }
+
+    pub fn render_revision(
+        &self,
+        article_id: i32,
+        revision: i32,
+        src: String,
+    ) -> CpuFuture<String, Error> {
+        self.execute(move |state| state.render_revision(article_id, revision, &src))
+    }
+
+    pub fn get_orphans(&self) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error> {
+        self.execute(move |state| state.get_orphans())
+    }
+
+    pub fn get_wanted_links(&self) -> CpuFuture<Vec<models::WantedLink>, Error> {
+        self.execute(move |state| state.get_wanted_links())
+    }
+
+    pub fn get_property_matches(
+        &self,
+        key: Option<String>,
+        value: Option<String>,
+    ) -> CpuFuture<Vec<models::PropertyMatch>, Error> {
+        self.execute(move |state| state.get_property_matches(key, value))
+    }
+
+    pub fn get_tags(&self) -> CpuFuture<Vec<(String, i64)>, Error> {
+        self.execute(move |state| state.get_tags())
+    }
+
+    pub fn get_tagged_articles(
+        &self,
+        tag: String,
+    ) -> CpuFuture<Vec<models::ArticleRevisionStub>, Error> {
+        self.execute(move |state| state.get_tagged_articles(tag))
+    }
+
+    pub fn search_query(
+        &self,
+        query_string: String,
+        tag: Option<String>,
+        limit: i32,
+        offset: i32,
+        snippet_size: i32,
+    ) -> CpuFuture<Vec<models::SearchResult>, Error> {
+        self.execute(move |state| {
+            state.search_query(query_string, tag, limit, offset, snippet_size)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db;
+
+    impl UpdateResult {
+        pub fn unwrap(self) -> models::ArticleRevision {
+            match self {
+                UpdateResult::Success(x) => x,
+                _ => panic!("Expected success"),
+            }
+        }
+    }
+
+    fn settings() -> Settings {
+        Settings {
+            max_rebase_distance: 100,
+            min_toc_headings: 4,
+            allowed_schemes: vec!["http".to_owned(), "https".to_owned()],
+        }
+    }
+
+    macro_rules! init {
+        ($state:ident) => {
+            init!($state, settings());
+        };
+        ($state:ident, $settings:expr) => {
+            let db = db::test_connection();
+            let settings = $settings;
+            let $state = SyncState::new(&db, &settings);
+        };
+    }
+
+    #[test]
+    fn get_article_slug() {
+        init!(state);
+        assert_matches!(state.get_article_slug(0), Ok(None));
+    }
+
+    #[test]
+    fn create_article() {
+        init!(state);
+        let article_revision = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        assert_eq!("title", article_revision.slug);
+        assert!(article_revision.latest);
+        assert_eq!(Theme::Cyan, article_revision.theme);
+    }
+
+    #[test]
+    fn create_article_when_empty_slug_then_empty_slug() {
+        // Front page gets to keep its empty slug
+        init!(state);
+        let article_revision = state
+            .create_article(
+                Some("".into()),
+                "Title".into(),
+                "Body".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        assert_eq!("", article_revision.slug);
+    }
+
+    #[test]
+    fn update_article() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let new_revision = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "New body".into(),
+                None,
+                Some(Theme::BlueGray),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(article.article_id, new_revision.article_id);
+
+        // Revision numbers must actually be sequential:
+        assert_eq!(article.revision + 1, new_revision.revision);
+
+        assert_eq!(article.title, new_revision.title);
+
+        // Slug must remain unchanged when the title is unchanged:
+        assert_eq!(article.slug, new_revision.slug);
+
+        assert_eq!("New body", new_revision.body);
+        assert_eq!(Theme::BlueGray, new_revision.theme);
+    }
+
+    #[test]
+    fn update_article_when_sequential_edits_then_last_wins() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let first_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "New body".into(),
+                None,
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let second_edit = state
+            .update_article(
+                article.article_id,
+                first_edit.revision,
+                article.title,
+                "Newer body".into(),
+                None,
+                Some(Theme::Amber),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!("Newer body", second_edit.body);
+        assert_eq!(Theme::Amber, second_edit.theme);
+    }
+
+    #[test]
+    fn update_article_when_edit_conflict_then_merge() {
+        init!(state);
+
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        let first_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nx\nb\nc\n".into(),
+                None,
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let second_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nb\ny\nc\n".into(),
+                None,
+                Some(Theme::Amber),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert!(article.revision < first_edit.revision);
+        assert!(first_edit.revision < second_edit.revision);
+
+        assert_eq!("a\nx\nb\ny\nc\n", second_edit.body);
+        assert_eq!(Theme::Amber, second_edit.theme);
+    }
+
+    #[test]
+    fn update_article_when_edit_conflict_then_rebase_over_multiple_revisions() {
+        init!(state);
+
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nx1\nb\nc\n".into(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let edit = state
+            .update_article(
+                article.article_id,
+                edit.revision,
+                article.title.clone(),
+                "a\nx1\nx2\nb\nc\n".into(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let edit = state
+            .update_article(
+                article.article_id,
+                edit.revision,
+                article.title.clone(),
+                "a\nx1\nx2\nx3\nb\nc\n".into(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        let rebase_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nb\ny\nc\n".into(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert!(article.revision < edit.revision);
+        assert!(edit.revision < rebase_edit.revision);
+
+        assert_eq!("a\nx1\nx2\nx3\nb\ny\nc\n", rebase_edit.body);
+    }
+
+    #[test]
+    fn update_article_when_title_edit_conflict_then_merge_title() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "titlle".into(), "".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let first_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                "Titlle".into(),
+                article.body.clone(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let second_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                "title".into(),
+                article.body.clone(),
+                None,
+                Some(article.theme),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert!(article.revision < first_edit.revision);
+        assert!(first_edit.revision < second_edit.revision);
+
+        assert_eq!("Title", second_edit.title);
+    }
+
+    #[test]
+    fn update_article_when_repeated_then_return_existing_revision() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "a".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "b".into(),
+                Some("Author".into()),
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let later_edit = state
+            .update_article(
+                article.article_id,
+                edit.revision,
+                article.title.clone(),
+                "c".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        let repeated_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "b".into(),
+                Some("Author".into()),
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(edit.revision, repeated_edit.revision);
+
+        let latest = state
+            .get_article_revision(article.article_id, later_edit.revision + 1)
+            .unwrap();
+        assert!(latest.is_none());
+    }
+
+    #[test]
+    fn update_article_when_rebased_edit_repeated_then_return_existing_revision() {
+        init!(state);
+
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "A\nb\nc\n".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let rebased_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nb\nC\n".into(),
+                Some("Author".into()),
+                None,
+                Some("Summary".into()),
+                true,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!("A\nb\nC\n", rebased_edit.body);
+
+        let repeated_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nb\nC\n".into(),
+                Some("Author".into()),
+                None,
+                Some("Summary".into()),
+                true,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(rebased_edit.revision, repeated_edit.revision);
+
+        // The same change with another summary is a new edit
+        let other_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nb\nC\n".into(),
+                Some("Author".into()),
+                None,
+                Some("Other summary".into()),
+                true,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(rebased_edit.revision + 1, other_edit.revision);
+    }
+
+    #[test]
+    fn update_article_when_base_revision_too_old_then_reject() {
+        init!(
+            state,
+            Settings {
+                max_rebase_distance: 3,
+                ..settings()
+            }
+        );
+
+        let article = state
+            .create_article(None, "Title".into(), "a".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let mut latest = article.revision;
+        for i in 0..=state.settings.max_rebase_distance {
+            latest = state
+                .update_article(
+                    article.article_id,
+                    latest,
+                    article.title.clone(),
+                    i.to_string(),
+                    None,
+                    None,
+                    None,
+                    false,
+                )
+                .unwrap()
+                .unwrap()
+                .revision;
+        }
+
+        let stale_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "b".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        assert_matches!(stale_edit, UpdateResult::RebaseTooLong);
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision + 1,
+                "New title".into(),
+                "0".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest + 1, edit.revision);
+        assert_eq!("New title", edit.title);
+    }
+
+    #[test]
+    fn update_article_with_summary() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        assert_eq!(None, article.summary);
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "New body".into(),
+                None,
+                None,
+                Some("Fix body".into()),
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some("Fix body"), edit.summary.as_deref());
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                edit.revision,
+                article.title.clone(),
+                "Newer body".into(),
+                None,
+                None,
+                Some("".into()),
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, edit.summary);
+    }
+
+    #[test]
+    fn update_article_when_minor() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        assert!(!article.minor);
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "Body.".into(),
+                None,
+                None,
+                None,
+                true,
+            )
+            .unwrap()
+            .unwrap();
+        assert!(edit.minor);
+    }
+
+    #[test]
+    fn update_article_when_merge_conflict() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "a".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let first_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "b".into(),
+                None,
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let conflict_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "c".into(),
+                None,
+                Some(Theme::Amber),
+                None,
+                false,
+            )
+            .unwrap();
+
+        match conflict_edit {
+            UpdateResult::Success(..) | UpdateResult::RebaseTooLong => panic!("Expected conflict"),
+            UpdateResult::RebaseConflict(RebaseConflict {
+                base_article,
+                title,
+                body,
+                theme,
+            }) => {
+                assert_eq!(first_edit.revision, base_article.revision);
+                assert_eq!(title, merge::MergeResult::Clean(article.title));
+                assert_eq!(
+                    body,
+                    merge::MergeResult::Conflicted(vec![merge::Output::Conflict(
+                        vec!["c"],
+                        vec!["a"],
+                        vec!["b"]
+                    ),])
+                    .into_strings()
+                );
+                assert_eq!(Theme::Amber, theme);
+            }
+        };
+    }
+
+    #[test]
+    fn update_article_when_theme_conflict_then_ignore_unchanged() {
+        init!(state);
+
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        let _first_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nx\nb\nc\n".into(),
+                None,
+                Some(Theme::Blue),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let second_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title,
+                "a\nb\ny\nc\n".into(),
+                None,
+                Some(Theme::Cyan),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(Theme::Blue, second_edit.theme);
+    }
+
+    #[test]
+    fn update_article_with_no_given_theme_then_theme_unchanged() {
+        init!(state);
+
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        let edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title,
+                article.body,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(Theme::Cyan, edit.theme);
+    }
+
+    #[test]
+    fn delete_article() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let tombstone = state
+            .delete_article(article.article_id, article.revision, None)
+            .unwrap();
+
+        assert_eq!(article.revision + 1, tombstone.revision);
+        assert!(tombstone.deleted);
+        assert!(tombstone.latest);
+
+        // History must stay intact:
+        assert_eq!(article.title, tombstone.title);
+        assert_eq!(article.body, tombstone.body);
+
+        assert_matches!(
+            state.lookup_slug(article.slug.clone()),
+            Ok(SlugLookup::Deleted { article_id, revision })
+                if article_id == article.article_id && revision == tombstone.revision
+        );
+    }
+
+    #[test]
+    fn delete_article_then_not_listed_or_searchable() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        assert_eq!(
+            1,
+            state
+                .search_query("body".into(), None, 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+
+        state
+            .delete_article(article.article_id, article.revision, None)
+            .unwrap();
+
+        assert_eq!(
+            0,
+            state
+                .search_query("body".into(), None, 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+
+        let latest = state
+            .query_article_revision_stubs(|query| {
+                query
+                    .filter(article_revisions::latest.eq(true))
+                    .filter(article_revisions::deleted.eq(false))
+            })
+            .unwrap();
+        assert!(latest.is_empty());
+    }
+
+    #[test]
+    fn delete_article_when_stale_base_revision_then_error() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "New body".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        assert!(state
+            .delete_article(article.article_id, article.revision, None)
+            .is_err());
+    }
+
+    #[test]
+    fn undelete_article() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        let tombstone = state
+            .delete_article(article.article_id, article.revision, None)
+            .unwrap();
+
+        let undeleted = state
+            .undelete_article(article.article_id, tombstone.revision, None)
+            .unwrap();
+
+        assert!(!undeleted.deleted);
+        assert_eq!(tombstone.revision + 1, undeleted.revision);
+        assert_eq!(article.slug, undeleted.slug);
+        assert_eq!(article.body, undeleted.body);
+        assert_eq!(
+            1,
+            state
+                .search_query("body".into(), None, 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+
+        assert_matches!(
+            state.lookup_slug(article.slug.clone()),
+            Ok(SlugLookup::Hit { revision, .. }) if revision == undeleted.revision
+        );
+    }
+    #[test]
+    fn revert_article() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+
+        let vandalized = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                "Vandalized".into(),
+                "Vandalized body".into(),
+                None,
+                Some(Theme::Red),
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!("vandalized", vandalized.slug);
+
+        let reverted = state
+            .revert_article(article.article_id, article.revision, None)
+            .unwrap();
+
+        assert_eq!(vandalized.revision + 1, reverted.revision);
+        assert_eq!(Some(article.revision), reverted.restored_revision);
+        assert_eq!(article.title, reverted.title);
+        assert_eq!(article.slug, reverted.slug);
+        assert_eq!(article.body, reverted.body);
+        assert_eq!(article.theme, reverted.theme);
+
+        let stored = state
+            .get_article_revision(article.article_id, reverted.revision)
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(article.revision), stored.restored_revision);
+
+        assert_eq!(
+            0,
+            state
+                .search_query("vandalized".into(), None, 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            1,
+            state
+                .search_query("body".into(), None, 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+    }
+
+    #[test]
+    fn revert_article_when_deleted_then_error() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan, None)
+            .unwrap();
+        let tombstone = state
+            .delete_article(article.article_id, article.revision, None)
+            .unwrap();
+
+        assert!(state
+            .revert_article(article.article_id, tombstone.revision, None)
+            .is_err());
+        assert!(state
+            .revert_article(article.article_id, article.revision, None)
+            .is_err());
+        assert!(state
+            .update_article(
+                article.article_id,
+                tombstone.revision,
+                "Title".into(),
+                "Edited".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .is_err());
+        assert!(state
+            .get_article_revision(article.article_id, tombstone.revision + 1)
+            .unwrap()
+            .is_none());
+
+        let undeleted = state
+            .undelete_article(article.article_id, tombstone.revision, None)
+            .unwrap();
+        let reverted = state
+            .revert_article(article.article_id, article.revision, None)
+            .unwrap();
+        assert!(!reverted.deleted);
+        assert_eq!(undeleted.revision + 1, reverted.revision);
     }
 
-    pub fn search_query(
-        &self,
-        query_string: String,
-        limit: i32,
-        offset: i32,
-        snippet_size: i32,
-    ) -> CpuFuture<Vec<models::SearchResult>, Error> {
-        self.execute(move |state| state.search_query(query_string, limit, offset, snippet_size))
-    }
-}
+    #[test]
+    fn undo_change() {
+        init!(state);
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::db;
+        let article = state
+            .create_article(
+                None,
+                "Title".into(),
+                "a\nb\nc\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        let bad_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "a\nx\nc\n".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let good_edit = state
+            .update_article(
+                article.article_id,
+                bad_edit.revision,
+                article.title.clone(),
+                "a\nx\nc\nd\n".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
 
-    impl UpdateResult {
-        pub fn unwrap(self) -> models::ArticleRevision {
-            match self {
-                UpdateResult::Success(x) => x,
-                _ => panic!("Expected success"),
-            }
-        }
+        let undone = state
+            .undo_change(article.article_id, bad_edit.revision, None)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(good_edit.revision + 1, undone.revision);
+        assert_eq!("a\nb\nc\nd\n", undone.body);
     }
 
-    macro_rules! init {
-        ($state:ident) => {
-            let db = db::test_connection();
-            let $state = SyncState::new(&db);
+    #[test]
+    fn undo_change_when_conflict() {
+        init!(state);
+
+        let article = state
+            .create_article(None, "Title".into(), "a".into(), None, Theme::Cyan, None)
+            .unwrap();
+        let bad_edit = state
+            .update_article(
+                article.article_id,
+                article.revision,
+                article.title.clone(),
+                "b".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let later_edit = state
+            .update_article(
+                article.article_id,
+                bad_edit.revision,
+                article.title.clone(),
+                "c".into(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+        match state
+            .undo_change(article.article_id, bad_edit.revision, None)
+            .unwrap()
+        {
+            UpdateResult::Success(..) | UpdateResult::RebaseTooLong => panic!("Expected conflict"),
+            UpdateResult::RebaseConflict(RebaseConflict {
+                base_article, body, ..
+            }) => {
+                assert_eq!(later_edit.revision, base_article.revision);
+                assert_eq!(
+                    body,
+                    merge::MergeResult::Conflicted(vec![merge::Output::Conflict(
+                        vec!["a"],
+                        vec!["b"],
+                        vec!["c"]
+                    ),])
+                    .into_strings()
+                );
+            }
         };
     }
 
     #[test]
-    fn get_article_slug() {
+    fn get_backlinks() {
         init!(state);
-        assert_matches!(state.get_article_slug(0), Ok(None));
+
+        let target = state
+            .create_article(
+                None,
+                "Target".into(),
+                "Body".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        let source = state
+            .create_article(
+                None,
+                "Source".into(),
+                "[Target]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        state
+            .create_article(
+                None,
+                "Other".into(),
+                "[Source]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+
+        let backlinks = state.get_backlinks(target.article_id).unwrap();
+        assert_eq!(1, backlinks.len());
+        assert_eq!(source.article_id, backlinks[0].article_id);
+
+        // Links to the old slug still lead to the renamed article
+        state
+            .update_article(
+                target.article_id,
+                target.revision,
+                "New target".into(),
+                target.body.clone(),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+        let backlinks = state.get_backlinks(target.article_id).unwrap();
+        assert_eq!(1, backlinks.len());
+
+        state
+            .delete_article(source.article_id, source.revision, None)
+            .unwrap();
+        let backlinks = state.get_backlinks(target.article_id).unwrap();
+        assert!(backlinks.is_empty());
     }
 
     #[test]
-    fn create_article() {
+    fn get_backlinks_from_many_articles() {
         init!(state);
-        let article_revision = state
-            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan)
+
+        let target = state
+            .create_article(None, "Target".into(), "".into(), None, Theme::Cyan, None)
             .unwrap();
-        assert_eq!("title", article_revision.slug);
-        assert!(article_revision.latest);
-        assert_eq!(Theme::Cyan, article_revision.theme);
+        // More sources than SQLite allows variables in a query
+        for i in 0..1000 {
+            state
+                .create_article(
+                    None,
+                    format!("Source {}", i),
+                    "[Target]".into(),
+                    None,
+                    Theme::Cyan,
+                    None,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(1000, state.get_backlinks(target.article_id).unwrap().len());
     }
 
     #[test]
-    fn create_article_when_empty_slug_then_empty_slug() {
-        // Front page gets to keep its empty slug
+    fn get_wanted_links() {
         init!(state);
-        let article_revision = state
+
+        state
             .create_article(
-                Some("".into()),
-                "Title".into(),
-                "Body".into(),
+                None,
+                "Source".into(),
+                "[Missing], [Existing], [Deleted]".into(),
                 None,
                 Theme::Cyan,
+                None,
             )
             .unwrap();
-        assert_eq!("", article_revision.slug);
+        state
+            .create_article(None, "Existing".into(), "".into(), None, Theme::Cyan, None)
+            .unwrap();
+        let deleted = state
+            .create_article(
+                None,
+                "Deleted".into(),
+                "[Other]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        state
+            .delete_article(deleted.article_id, deleted.revision, None)
+            .unwrap();
+
+        let wanted = state.get_wanted_links().unwrap();
+        assert_eq!(1, wanted.len());
+        assert_eq!("missing", wanted[0].target_slug);
+        assert_eq!("source", wanted[0].slug);
     }
 
     #[test]
-    fn update_article() {
+    fn get_orphans() {
         init!(state);
 
-        let article = state
-            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan)
+        state
+            .create_article(
+                Some("".into()),
+                "Front page".into(),
+                "[Linked]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        let linked = state
+            .create_article(
+                None,
+                "Linked".into(),
+                "[Linked]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        let orphan = state
+            .create_article(
+                None,
+                "Orphan".into(),
+                "[Orphan]".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
             .unwrap();
 
-        let new_revision = state
+        let orphans = state.get_orphans().unwrap();
+        assert_eq!(1, orphans.len());
+        assert_eq!(orphan.article_id, orphans[0].article_id);
+
+        state
+            .delete_article(orphan.article_id, orphan.revision, None)
+            .unwrap();
+        state
             .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "New body".into(),
+                linked.article_id,
+                linked.revision,
+                "Renamed".into(),
+                linked.body.clone(),
                 None,
-                Some(Theme::BlueGray),
+                None,
+                None,
+                false,
             )
             .unwrap()
             .unwrap();
 
-        assert_eq!(article.article_id, new_revision.article_id);
-
-        // Revision numbers must actually be sequential:
-        assert_eq!(article.revision + 1, new_revision.revision);
+        let orphans = state.get_orphans().unwrap();
+        assert!(orphans.is_empty());
+    }
 
-        assert_eq!(article.title, new_revision.title);
+    #[test]
+    fn get_orphans_with_many_links() {
+        init!(state);
 
-        // Slug must remain unchanged when the title is unchanged:
-        assert_eq!(article.slug, new_revision.slug);
+        // More linked articles than SQLite allows variables in a query
+        for i in 0..1000 {
+            state
+                .create_article(
+                    None,
+                    format!("A{}", i),
+                    format!("[A{}]", (i + 1) % 1000),
+                    None,
+                    Theme::Cyan,
+                    None,
+                )
+                .unwrap();
+        }
 
-        assert_eq!("New body", new_revision.body);
-        assert_eq!(Theme::BlueGray, new_revision.theme);
+        assert!(state.get_orphans().unwrap().is_empty());
     }
 
     #[test]
-    fn update_article_when_sequential_edits_then_last_wins() {
+    fn render_markdown_marks_missing_links() {
         init!(state);
 
-        let article = state
-            .create_article(None, "Title".into(), "Body".into(), None, Theme::Cyan)
+        state
+            .create_article(None, "Existing".into(), "".into(), None, Theme::Cyan, None)
+            .unwrap();
+        let deleted = state
+            .create_article(None, "Deleted".into(), "".into(), None, Theme::Cyan, None)
+            .unwrap();
+        state
+            .delete_article(deleted.article_id, deleted.revision, None)
             .unwrap();
 
-        let first_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "New body".into(),
+        let rendered = state
+            .render_markdown(None, "[Missing], [Existing], [Deleted]")
+            .unwrap();
+        assert!(rendered.contains("<a href=\"missing\" class=\"missing-article\""));
+        assert!(rendered.contains("<a href=\"existing\" title=\"Existing\">"));
+        assert!(rendered.contains("<a href=\"deleted\" class=\"missing-article\""));
+    }
+
+    #[test]
+    fn render_markdown_includes_articles() {
+        init!(state);
+
+        let a = state
+            .create_article(
+                None,
+                "A".into(),
+                "{{include:B}}".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        state
+            .create_article(
+                None,
+                "B".into(),
+                "b\n\n{{include:A}}".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        state
+            .create_article(
+                None,
+                "C".into(),
+                "{{include:D}}".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
+            .unwrap();
+        state
+            .create_article(
+                None,
+                "D".into(),
+                "{{include:E}}".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(Theme::Blue),
             )
-            .unwrap()
             .unwrap();
-        let second_edit = state
-            .update_article(
-                article.article_id,
-                first_edit.revision,
-                article.title,
-                "Newer body".into(),
+        state
+            .create_article(
+                None,
+                "E".into(),
+                "{{include:F}}".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(Theme::Amber),
             )
-            .unwrap()
+            .unwrap();
+        state
+            .create_article(None, "F".into(), "f".into(), None, Theme::Cyan, None)
             .unwrap();
 
-        assert_eq!("Newer body", second_edit.body);
-        assert_eq!(Theme::Amber, second_edit.theme);
+        let rendered = state
+            .render_markdown(Some(a.article_id), "{{include:B}}")
+            .unwrap();
+        assert!(rendered.contains("<p>b</p>"));
+        assert!(
+            rendered.contains("Cannot include <a href=\"a\">a</a>: the article includes itself")
+        );
+
+        let rendered = state.render_markdown(None, "{{include:C}}").unwrap();
+        assert!(
+            rendered.contains("Cannot include <a href=\"f\">f</a>: includes are nested too deeply")
+        );
+        assert!(!rendered.contains("<p>f</p>"));
+
+        let rendered = state.render_markdown(None, "{{include:D}}").unwrap();
+        assert!(rendered.contains("<p>f</p>"));
     }
 
     #[test]
-    fn update_article_when_edit_conflict_then_merge() {
+    fn render_markdown_includes_limited_number_of_articles() {
         init!(state);
 
-        let article = state
-            .create_article(None, "Title".into(), "a\nb\nc\n".into(), None, Theme::Cyan)
-            .unwrap();
+        let leaves = (0..8)
+            .map(|i| format!("{{{{include:L{}}}}}", i))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let middles = (0..8)
+            .map(|i| format!("{{{{include:M{}}}}}", i))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        for i in 0..8 {
+            state
+                .create_article(
+                    None,
+                    format!("L{}", i),
+                    "leaf".into(),
+                    None,
+                    Theme::Cyan,
+                    None,
+                )
+                .unwrap();
+            state
+                .create_article(
+                    None,
+                    format!("M{}", i),
+                    leaves.clone(),
+                    None,
+                    Theme::Cyan,
+                    None,
+                )
+                .unwrap();
+        }
 
-        let first_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "a\nx\nb\nc\n".into(),
+        // Of the 8 + 8 * 8 includes, the first 50 are expanded: five middle
+        // articles with all their leaves, and the sixth with four leaves.
+        // The last four leaves of the sixth and the last two middle articles
+        // are errors.
+        let rendered = state.render_markdown(None, &middles).unwrap();
+        assert_eq!(5 * 8 + 4, rendered.matches("<p>leaf</p>").count());
+        assert_eq!(
+            4 + 2,
+            rendered.matches("too many articles are included").count()
+        );
+    }
+
+    #[test]
+    fn render_markdown_expands_macros() {
+        init!(state);
+
+        let team = state
+            .create_article(None, "Team".into(), "".into(), None, Theme::Cyan, None)
+            .unwrap();
+        state
+            .create_article(
+                None,
+                "On call".into(),
+                "---\nparent: Team\ntags: ops\n---\nCall us".into(),
+                Some("alice".into()),
+                Theme::Cyan,
                 None,
-                Some(Theme::Blue),
             )
-            .unwrap()
             .unwrap();
-        let second_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "a\nb\ny\nc\n".into(),
+        state
+            .create_article(
+                None,
+                "Other".into(),
+                "---\nparent: Elsewhere\n---\nCall them".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(Theme::Amber),
             )
-            .unwrap()
             .unwrap();
 
-        assert!(article.revision < first_edit.revision);
-        assert!(first_edit.revision < second_edit.revision);
+        let rendered = state
+            .render_markdown(Some(team.article_id), "{{children}}")
+            .unwrap();
+        assert!(rendered.contains("<a href=\"on-call\">On call</a>"));
+        assert!(!rendered.contains("Other"));
 
-        assert_eq!("a\nx\nb\ny\nc\n", second_edit.body);
-        assert_eq!(Theme::Amber, second_edit.theme);
+        let rendered = state
+            .render_markdown(None, "{{article-list tag=ops}}")
+            .unwrap();
+        assert!(rendered.contains("<a href=\"on-call\">On call</a>"));
+        assert!(!rendered.contains("Other"));
+
+        let rendered = state
+            .render_markdown(None, "{{recent-changes n=2}}")
+            .unwrap();
+        assert!(rendered.contains("<a href=\"other\">Other</a>"));
+        assert!(rendered.contains("<a href=\"on-call\">On call</a>"));
+        assert!(rendered.contains(" by alice</span>"));
+        assert!(!rendered.contains("Team"));
+
+        let rendered = state.render_markdown(None, "{{search q=them}}").unwrap();
+        assert!(rendered.contains("<a href=\"other\">Other</a>"));
+        assert!(!rendered.contains("On call"));
     }
 
     #[test]
-    fn update_article_when_edit_conflict_then_rebase_over_multiple_revisions() {
+    fn render_revision_is_cached() {
         init!(state);
 
         let article = state
-            .create_article(None, "Title".into(), "a\nb\nc\n".into(), None, Theme::Cyan)
-            .unwrap();
-
-        let edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "a\nx1\nb\nc\n".into(),
+            .create_article(
                 None,
-                Some(article.theme),
-            )
-            .unwrap()
-            .unwrap();
-        let edit = state
-            .update_article(
-                article.article_id,
-                edit.revision,
-                article.title.clone(),
-                "a\nx1\nx2\nb\nc\n".into(),
+                "Title".into(),
+                "[Other]".into(),
                 None,
-                Some(article.theme),
-            )
-            .unwrap()
-            .unwrap();
-        let edit = state
-            .update_article(
-                article.article_id,
-                edit.revision,
-                article.title.clone(),
-                "a\nx1\nx2\nx3\nb\nc\n".into(),
+                Theme::Cyan,
                 None,
-                Some(article.theme),
             )
-            .unwrap()
             .unwrap();
+        let render = || {
+            state
+                .render_revision(article.article_id, article.revision, &article.body)
+                .unwrap()
+        };
+        assert!(render().contains("missing-article"));
+
+        diesel::update(render_cache::table)
+            .set(render_cache::html.eq("cached"))
+            .execute(state.db_connection)
+            .unwrap();
+        assert_eq!(render(), "cached");
 
-        let rebase_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "a\nb\ny\nc\n".into(),
-                None,
-                Some(article.theme),
-            )
-            .unwrap()
+        state
+            .create_article(None, "Other".into(), "".into(), None, Theme::Cyan, None)
             .unwrap();
+        assert!(!render().contains("missing-article"));
 
-        assert!(article.revision < edit.revision);
-        assert!(edit.revision < rebase_edit.revision);
+        diesel::update(render_cache::table)
+            .set(render_cache::renderer_version.eq("old"))
+            .execute(state.db_connection)
+            .unwrap();
+        assert!(render().contains("href=\"other\""));
 
-        assert_eq!("a\nx1\nx2\nx3\nb\ny\nc\n", rebase_edit.body);
+        diesel::update(render_cache::table)
+            .set(render_cache::html.eq("cached"))
+            .execute(state.db_connection)
+            .unwrap();
+        let settings = Settings {
+            allowed_schemes: vec![],
+            ..settings()
+        };
+        let other = SyncState::new(state.db_connection, &settings);
+        let rendered = other
+            .render_revision(article.article_id, article.revision, &article.body)
+            .unwrap();
+        assert!(rendered.contains("href=\"other\""));
+
+        state
+            .render_revision(article.article_id + 1, 1, "{{recent-changes}}")
+            .unwrap();
+        let cached = render_cache::table
+            .count()
+            .get_result::<i64>(state.db_connection)
+            .unwrap();
+        assert_eq!(cached, 1);
     }
 
     #[test]
-    fn update_article_when_title_edit_conflict_then_merge_title() {
+    fn get_tags() {
         init!(state);
 
-        let article = state
-            .create_article(None, "titlle".into(), "".into(), None, Theme::Cyan)
+        let tagged = state
+            .create_article(
+                None,
+                "Tagged".into(),
+                "---\ntags: a, b\n---\nbody".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
             .unwrap();
-
-        let first_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                "Titlle".into(),
-                article.body.clone(),
+        state
+            .create_article(
+                None,
+                "Other".into(),
+                "---\ntags: b\n---\nbody".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(article.theme),
             )
-            .unwrap()
             .unwrap();
-        let second_edit = state
+
+        assert_eq!(
+            vec![("a".to_owned(), 1), ("b".to_owned(), 2)],
+            state.get_tags().unwrap()
+        );
+
+        let articles = state.get_tagged_articles("b".into()).unwrap();
+        assert_eq!(
+            vec!["Other", "Tagged"],
+            articles.iter().map(|x| &x.title).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            1,
+            state
+                .search_query("body".into(), Some("a".into()), 10, 0, 10)
+                .unwrap()
+                .len()
+        );
+
+        state
             .update_article(
-                article.article_id,
-                article.revision,
-                "title".into(),
-                article.body.clone(),
+                tagged.article_id,
+                tagged.revision,
+                tagged.title.clone(),
+                "body".into(),
                 None,
-                Some(article.theme),
+                None,
+                None,
+                false,
             )
-            .unwrap()
             .unwrap();
 
-        assert!(article.revision < first_edit.revision);
-        assert!(first_edit.revision < second_edit.revision);
-
-        assert_eq!("Title", second_edit.title);
+        assert_eq!(vec![("b".to_owned(), 1)], state.get_tags().unwrap());
     }
 
     #[test]
-    fn update_article_when_merge_conflict() {
+    fn get_tagged_articles_with_many_articles() {
         init!(state);
 
-        let article = state
-            .create_article(None, "Title".into(), "a".into(), None, Theme::Cyan)
-            .unwrap();
+        // More tagged articles than SQLite allows variables in a query
+        for i in 0..1000 {
+            state
+                .create_article(
+                    None,
+                    format!("A{}", i),
+                    "---\ntags: many\n---\nbody".into(),
+                    None,
+                    Theme::Cyan,
+                    None,
+                )
+                .unwrap();
+        }
 
-        let first_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "b".into(),
+        assert_eq!(
+            1000,
+            state.get_tagged_articles("many".into()).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn get_property_matches() {
+        init!(state);
+
+        let draft = state
+            .create_article(
+                None,
+                "Draft".into(),
+                "---\nstatus: draft\nowner: alice\n---\n".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(Theme::Blue),
             )
-            .unwrap()
             .unwrap();
-        let conflict_edit = state
-            .update_article(
-                article.article_id,
-                article.revision,
-                article.title.clone(),
-                "c".into(),
+        state
+            .create_article(
+                None,
+                "Final".into(),
+                "+++\nstatus = \"final\"\n+++\n".into(),
+                None,
+                Theme::Cyan,
                 None,
-                Some(Theme::Amber),
             )
             .unwrap();
 
-        match conflict_edit {
-            UpdateResult::Success(..) => panic!("Expected conflict"),
-            UpdateResult::RebaseConflict(RebaseConflict {
-                base_article,
-                title,
-                body,
-                theme,
-            }) => {
-                assert_eq!(first_edit.revision, base_article.revision);
-                assert_eq!(title, merge::MergeResult::Clean(article.title));
-                assert_eq!(
-                    body,
-                    merge::MergeResult::Conflicted(vec![merge::Output::Conflict(
-                        vec!["c"],
-                        vec!["a"],
-                        vec!["b"]
-                    ),])
-                    .into_strings()
-                );
-                assert_eq!(Theme::Amber, theme);
-            }
-        };
+        let matches = state
+            .get_property_matches(Some("status".into()), Some("draft".into()))
+            .unwrap();
+        assert_eq!(1, matches.len());
+        assert_eq!("Draft", matches[0].title);
+
+        let matches = state
+            .get_property_matches(Some("status".into()), None)
+            .unwrap();
+        assert_eq!(
+            vec!["draft", "final"],
+            matches.iter().map(|x| &x.value).collect::<Vec<_>>()
+        );
+
+        state
+            .delete_article(draft.article_id, draft.revision, None)
+            .unwrap();
+
+        assert_eq!(1, state.get_property_matches(None, None).unwrap().len());
     }
 
     #[test]
-    fn update_article_when_theme_conflict_then_ignore_unchanged() {
+    fn update_article_section_when_concurrent_edit_then_merge() {
         init!(state);
 
         let article = state
-            .create_article(None, "Title".into(), "a\nb\nc\n".into(), None, Theme::Cyan)
+            .create_article(
+                None,
+                "Title".into(),
+                "# One\n\na\n\n# Two\n\nb\n".into(),
+                None,
+                Theme::Cyan,
+                None,
+            )
             .unwrap();
 
-        let _first_edit = state
+        state
             .update_article(
                 article.article_id,
                 article.revision,
                 article.title.clone(),
-                "a\nx\nb\nc\n".into(),
+                "# One\n\nc\n\n# Two\n\nb\n".into(),
                 None,
-                Some(Theme::Blue),
+                None,
+                None,
+                false,
             )
-            .unwrap()
             .unwrap();
-        let second_edit = state
-            .update_article(
+
+        let updated = state
+            .update_article_section(
                 article.article_id,
                 article.revision,
-                article.title,
-                "a\nb\ny\nc\n".into(),
+                2,
+                article.title.clone(),
+                "# Two\n\nd\n".into(),
                 None,
-                Some(Theme::Cyan),
+                None,
+                None,
+                false,
             )
             .unwrap()
             .unwrap();
 
-        assert_eq!(Theme::Blue, second_edit.theme);
-    }
-
-    #[test]
-    fn update_article_with_no_given_theme_then_theme_unchanged() {
-        init!(state);
-
-        let article = state
-            .create_article(None, "Title".into(), "a\nb\nc\n".into(), None, Theme::Cyan)
-            .unwrap();
+        assert_eq!("# One\n\nc\n\n# Two\n\nd\n", updated.body);
 
-        let edit = state
-            .update_article(
+        assert!(state
+            .update_article_section(
                 article.article_id,
-                article.revision,
-                article.title,
-                article.body,
+                updated.revision,
+                3,
+                article.title.clone(),
+                "".into(),
+                None,
                 None,
                 None,
+                false,
             )
-            .unwrap()
-            .unwrap();
-
-        assert_eq!(Theme::Cyan, edit.theme);
+            .is_err());
     }
 }
@@ -2,6 +2,8 @@ use futures::{Future, Stream};
 use hyper::server::Response;
 use hyper::{self, header, mime, server};
 
+use crate::rate_limit::Client;
+
 lazy_static! {
     static ref TEXT_PLAIN: mime::Mime = "text/plain;charset=utf-8".parse().unwrap();
 }
@@ -60,4 +62,9 @@ pub trait Resource {
         // This function is a complete hack, searching for the appropriate
         // architecture.
     }
+
+    fn hacky_inject_client(&mut self, _: Client) {
+        // Like `hacky_inject_accept_header`, for the resources that limit
+        // how often each client can use them
+    }
 }
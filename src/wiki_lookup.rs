@@ -42,6 +42,7 @@ pub struct WikiLookup {
     state: State,
     changes_lookup: ChangesLookup,
     diff_lookup: DiffLookup,
+    properties_lookup: PropertiesLookup,
     search_lookup: SearchLookup,
 }
 
@@ -54,6 +55,20 @@ fn split_one(path: &str) -> Result<(Cow<str>, Option<&str>), Utf8Error> {
     Ok((head, tail))
 }
 
+// `?format=name` selects a download format. Unknown formats are not found.
+fn format_from_query(query: Option<&str>) -> Result<Option<ArticleFormat>, ()> {
+    #[derive(Deserialize)]
+    struct QueryParameters {
+        format: Option<String>,
+    }
+
+    let args: QueryParameters = serde_urlencoded::from_str(query.unwrap_or("")).map_err(|_| ())?;
+    match args.format {
+        Some(name) => ArticleFormat::from_name(&name).map(Some).ok_or(()),
+        None => Ok(None),
+    }
+}
+
 fn map_lookup(
     map: &HashMap<&str, ResourceFn>,
     path: &str,
@@ -108,36 +123,42 @@ impl WikiLookup {
     pub fn new(state: State, show_authors: bool) -> WikiLookup {
         let changes_lookup = ChangesLookup::new(state.clone(), show_authors);
         let diff_lookup = DiffLookup::new(state.clone());
+        let properties_lookup = PropertiesLookup::new(state.clone());
         let search_lookup = SearchLookup::new(state.clone());
 
         WikiLookup {
             state,
             changes_lookup,
             diff_lookup,
+            properties_lookup,
             search_lookup,
         }
     }
 
-    fn revisions_lookup(&self, path: &str, _query: Option<&str>) -> <Self as Lookup>::Future {
-        let (article_id, revision): (i32, i32) = match (|| -> Result<_, <Self as Lookup>::Error> {
+    fn revisions_lookup(&self, path: &str, query: Option<&str>) -> <Self as Lookup>::Future {
+        let (article_id, revision, format) = match (|| -> Result<_, <Self as Lookup>::Error> {
             let (article_id, tail) = split_one(path)?;
             let (revision, tail) = split_one(tail.ok_or("Not found")?)?;
             if tail.is_some() {
                 return Err("Not found".into());
             }
+            let format = format_from_query(query).map_err(|_| "Not found")?;
 
-            Ok((article_id.parse()?, revision.parse()?))
+            Ok((article_id.parse::<i32>()?, revision.parse::<i32>()?, format))
         })() {
             Ok(x) => x,
             Err(_) => return Box::new(finished(None)),
         };
 
+        let state = self.state.clone();
+
         Box::new(
             self.state
                 .get_article_revision(article_id, revision)
-                .and_then(|article_revision| {
-                    Ok(article_revision
-                        .map(move |x| Box::new(ArticleRevisionResource::new(x)) as BoxResource))
+                .and_then(move |article_revision| {
+                    Ok(article_revision.map(move |x| {
+                        Box::new(ArticleRevisionResource::new(state, x, format)) as BoxResource
+                    }))
                 }),
         )
     }
@@ -162,6 +183,44 @@ impl WikiLookup {
         }))
     }
 
+    fn backlinks_lookup(&self, path: &str, _query: Option<&str>) -> <Self as Lookup>::Future {
+        let article_id: i32 = match (|| -> Result<_, <Self as Lookup>::Error> {
+            let (article_id, tail) = split_one(path)?;
+            if tail.is_some() {
+                return Err("Not found".into());
+            }
+
+            Ok(article_id.parse()?)
+        })() {
+            Ok(x) => x,
+            Err(_) => return Box::new(finished(None)),
+        };
+
+        let state = self.state.clone();
+
+        Box::new(
+            self.state
+                .get_article_slug(article_id)
+                .and_then(move |slug| {
+                    Ok(slug.map(|_| {
+                        Box::new(BacklinksResource::new(state, article_id)) as BoxResource
+                    }))
+                }),
+        )
+    }
+
+    fn tag_lookup(&self, path: &str, _query: Option<&str>) -> <Self as Lookup>::Future {
+        let tag = match split_one(path) {
+            // Tags are stored as slugs
+            Ok((tag, None)) => slugify(tag),
+            _ => return Box::new(finished(None)),
+        };
+
+        Box::new(finished(Some(
+            Box::new(TagResource::new(self.state.clone(), tag)) as BoxResource,
+        )))
+    }
+
     fn diff_lookup_f(&self, path: &str, query: Option<&str>) -> <Self as Lookup>::Future {
         let article_id: u32 = match (|| -> Result<_, <Self as Lookup>::Error> {
             let (article_id, tail) = split_one(path)?;
@@ -196,6 +255,7 @@ impl WikiLookup {
             )),
             #[cfg(not(feature = "dynamic-assets"))]
             ("_assets", Some(asset)) => Box::new(map_lookup(&ASSETS_MAP, asset)),
+            ("_backlinks", Some(tail)) => self.backlinks_lookup(tail, query),
             ("_by_id", Some(tail)) => self.by_id_lookup(tail, query),
             ("_changes", None) => Box::new(self.changes_lookup.lookup(query)),
             ("_diff", Some(tail)) => self.diff_lookup_f(tail, query),
@@ -204,11 +264,25 @@ impl WikiLookup {
                 None,
                 true,
             )) as BoxResource))),
+            ("_orphans", None) => Box::new(finished(Some(Box::new(OrphansResource::new(
+                self.state.clone(),
+            )) as BoxResource))),
+            ("_preview", None) => Box::new(finished(Some(Box::new(PreviewResource::new(
+                self.state.clone(),
+            )) as BoxResource))),
+            ("_properties", None) => Box::new(done(self.properties_lookup.lookup(query))),
             ("_revisions", Some(tail)) => self.revisions_lookup(tail, query),
             ("_search", None) => Box::new(done(self.search_lookup.lookup(query))),
             ("_sitemap", None) => Box::new(finished(Some(Box::new(SitemapResource::new(
                 self.state.clone(),
             )) as BoxResource))),
+            ("_tags", None) => Box::new(finished(Some(Box::new(TagsResource::new(
+                self.state.clone(),
+            )) as BoxResource))),
+            ("_tags", Some(tail)) => self.tag_lookup(tail, query),
+            ("_wanted", None) => Box::new(finished(Some(Box::new(WantedResource::new(
+                self.state.clone(),
+            )) as BoxResource))),
             _ => Box::new(finished(None)),
         }
     }
@@ -224,7 +298,16 @@ impl WikiLookup {
             return Box::new(finished(None));
         }
 
-        let edit = query == Some("edit");
+        // `?edit&section=N` opens the editor for only the Nth section
+        let section = query
+            .and_then(|x| x.strip_prefix("edit&section="))
+            .and_then(|x| x.parse().ok());
+        let edit = query == Some("edit") || section.is_some();
+        let delete = query == Some("delete");
+        let format = match format_from_query(query) {
+            Ok(x) => x,
+            Err(()) => return Box::new(finished(None)),
+        };
 
         // Normalize all user-generated slugs:
         let slugified_slug = slugify(&slug);
@@ -246,9 +329,20 @@ impl WikiLookup {
                 SlugLookup::Hit {
                     article_id,
                     revision,
-                } => {
-                    Box::new(ArticleResource::new(state, article_id, revision, edit)) as BoxResource
+                } if delete => {
+                    Box::new(DeleteArticleResource::new(state, article_id, revision)) as BoxResource
                 }
+                SlugLookup::Hit {
+                    article_id,
+                    revision,
+                } => Box::new(ArticleResource::new(
+                    state, article_id, revision, edit, section, format,
+                )) as BoxResource,
+                SlugLookup::Deleted {
+                    article_id,
+                    revision,
+                } => Box::new(DeletedArticleResource::new(state, article_id, revision))
+                    as BoxResource,
                 SlugLookup::Redirect(slug) => {
                     Box::new(TemporaryRedirectResource::from_slug(slug, edit)) as BoxResource
                 }